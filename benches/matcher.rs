@@ -0,0 +1,63 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use evdev_shortcut::{Key, Modifier, Shortcut, ShortcutMatcher};
+use std::convert::TryFrom;
+use std::time::SystemTime;
+
+const MODIFIERS: &[&[Modifier]] = &[
+    &[],
+    &[Modifier::Ctrl],
+    &[Modifier::Alt],
+    &[Modifier::Shift],
+    &[Modifier::Meta],
+    &[Modifier::Ctrl, Modifier::Shift],
+    &[Modifier::Ctrl, Modifier::Alt],
+    &[Modifier::Meta, Modifier::Shift],
+];
+
+/// Create a matcher with the shortcuts used while typing, and `count` distinct shortcuts spread over the keyboard
+fn matcher(count: usize) -> ShortcutMatcher {
+    let mut matcher = ShortcutMatcher::new();
+    matcher.add(Shortcut::new(&[Modifier::Ctrl, Modifier::Shift], Key::KeyP));
+    matcher.add(Shortcut::new(&[], Key::KeyH));
+    let keys = (1..0x2ff).filter_map(|code| Key::try_from(code).ok())
+        .filter(|key| Modifier::mask_from_key(*key) == 0);
+    for (key, modifiers) in keys.flat_map(|key| MODIFIERS.iter().map(move |modifiers| (key, modifiers))).take(count) {
+        matcher.add(Shortcut::new(modifiers, key));
+    }
+    matcher
+}
+
+fn typing(c: &mut Criterion) {
+    // typing a shortcut, followed by some regular key presses
+    let events = [
+        (Key::KeyLeftCtrl, 1),
+        (Key::KeyLeftShift, 1),
+        (Key::KeyP, 1),
+        (Key::KeyP, 2),
+        (Key::KeyP, 0),
+        (Key::KeyLeftShift, 0),
+        (Key::KeyLeftCtrl, 0),
+        (Key::KeyH, 1),
+        (Key::KeyH, 0),
+        (Key::KeyI, 1),
+        (Key::KeyI, 0),
+    ];
+
+    let mut group = c.benchmark_group("handle_key");
+    for count in [10, 100, 1000, 5000] {
+        let mut matcher = matcher(count);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &events, |b, events| {
+            b.iter(|| {
+                for (key, value) in events {
+                    for event in matcher.handle_key(*key, *value, SystemTime::UNIX_EPOCH) {
+                        black_box(event);
+                    }
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, typing);
+criterion_main!(benches);
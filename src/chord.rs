@@ -0,0 +1,154 @@
+use crate::{Key, Modifier, ShortcutState};
+use parse_display::ParseError;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Two or more keys that are pressed together, in any order
+///
+/// A chord triggers when all of its keys are held, the last key being pressed within the
+/// [chord window](crate::ShortcutMatcher::set_chord_window) of the first, and no modifiers are held.
+/// It's released as soon as one of its keys is released.
+///
+/// The keys of a chord can't be modifiers, the keys are kept ordered by key code.
+///
+/// The textual form joins the keys with `+`.
+///
+/// Example:
+///
+/// ```rust
+/// # use evdev_shortcut::{Chord, Key};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let escape: Chord = "KeyK+KeyJ".parse()?;
+/// assert_eq!(&[Key::KeyJ, Key::KeyK], escape.keys());
+/// assert_eq!(escape, Chord::new(&[Key::KeyJ, Key::KeyK])?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Chord {
+    keys: Vec<Key>,
+}
+
+impl Chord {
+    /// Create a chord from at least two different keys, none of which can be a modifier
+    pub fn new(keys: &[Key]) -> Result<Self, InvalidChord> {
+        let mut keys = keys.to_vec();
+        keys.sort_by_key(|key| *key as u16);
+        keys.dedup();
+        if keys.len() < 2 {
+            return Err(InvalidChord::TooFewKeys);
+        }
+        if let Some(modifier) = keys.iter().find(|key| Modifier::mask_from_key(**key) != 0) {
+            return Err(InvalidChord::Modifier(*modifier));
+        }
+        Ok(Chord { keys })
+    }
+
+    /// The keys of the chord, ordered by key code
+    pub fn keys(&self) -> &[Key] {
+        &self.keys
+    }
+
+    pub fn contains(&self, key: Key) -> bool {
+        self.keys.contains(&key)
+    }
+
+    /// Check if all keys of the other chord are part of this chord
+    pub fn includes(&self, other: &Chord) -> bool {
+        other.keys.iter().all(|key| self.contains(*key))
+    }
+}
+
+/// Error returned when creating a chord from keys that can't form a chord
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum InvalidChord {
+    #[error("A chord needs at least two different keys")]
+    TooFewKeys,
+    #[error("A chord can't contain modifier {0}")]
+    Modifier(Key),
+}
+
+/// Error returned when a chord can't be told apart from an already registered chord
+///
+/// A chord conflicts with the chords that contain all of its keys, or of which it contains all keys,
+/// since pressing the larger chord would trigger both.
+#[derive(Debug, Clone, Error)]
+#[error("{chord} overlaps with {existing}")]
+pub struct ChordConflict {
+    pub chord: Chord,
+    pub existing: Chord,
+}
+
+impl FromStr for Chord {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let keys = s
+            .split('+')
+            .map(Key::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        Chord::new(&keys).map_err(|error| match error {
+            InvalidChord::TooFewKeys => ParseError::with_message("A chord needs at least two different keys"),
+            InvalidChord::Modifier(_) => ParseError::with_message("A chord can't contain modifiers"),
+        })
+    }
+}
+
+impl Display for Chord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (index, key) in self.keys.iter().enumerate() {
+            if index > 0 {
+                write!(f, "+")?;
+            }
+            write!(f, "{}", key)?;
+        }
+        Ok(())
+    }
+}
+
+/// Event emitted when a chord is pressed or released
+///
+/// The state is either [Pressed](ShortcutState::Pressed) or [Released](ShortcutState::Released).
+#[derive(Debug, Clone)]
+pub struct ChordEvent {
+    pub chord: Chord,
+    pub state: ShortcutState,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Chord, InvalidChord};
+    use crate::Key;
+    use test_case::test_case;
+
+    #[test_case("KeyJ+KeyK", & [Key::KeyJ, Key::KeyK])]
+    #[test_case("KeyA+KeyS+KeyD", & [Key::KeyS, Key::KeyD, Key::KeyA])]
+    fn chord_parse_display_test(s: &str, keys: &[Key]) {
+        let chord = Chord::new(keys).unwrap();
+        assert_eq!(s, chord.to_string());
+        assert_eq!(chord, s.parse().unwrap());
+    }
+
+    #[test]
+    fn chord_parse_order() {
+        assert_eq!("KeyJ+KeyK".parse::<Chord>().unwrap(), "KeyK+KeyJ".parse().unwrap());
+    }
+
+    #[test_case("KeyJ")]
+    #[test_case("KeyJ+KeyJ")]
+    #[test_case("KeyJ+")]
+    #[test_case("KeyJ+KeyLeftCtrl")]
+    #[test_case("<Ctrl>-KeyJ+KeyK")]
+    fn chord_parse_error(s: &str) {
+        assert!(s.parse::<Chord>().is_err());
+    }
+
+    #[test_case(& [Key::KeyJ] => Err(InvalidChord::TooFewKeys))]
+    #[test_case(& [Key::KeyJ, Key::KeyJ] => Err(InvalidChord::TooFewKeys))]
+    #[test_case(& [Key::KeyJ, Key::KeyLeftCtrl] => Err(InvalidChord::Modifier(Key::KeyLeftCtrl)))]
+    #[test_case(& [Key::KeyK, Key::KeyJ] => Ok(vec![Key::KeyJ, Key::KeyK]))]
+    fn chord_new_test(keys: &[Key]) -> Result<Vec<Key>, InvalidChord> {
+        Chord::new(keys).map(|chord| chord.keys().to_vec())
+    }
+}
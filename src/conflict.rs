@@ -0,0 +1,213 @@
+use crate::{KeySequence, KeySet, Modifier, Shortcut, MODIFIER_GROUPS};
+use thiserror::Error;
+
+/// Problem found when analyzing a set of shortcuts
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ShortcutConflict {
+    /// The same shortcut is bound more than once
+    #[error("{0} is bound more than once")]
+    Duplicate(Shortcut),
+    /// Both shortcuts are triggered by at least one combination of keys
+    #[error("{shortcut} and {other} can be triggered by the same keys")]
+    Overlap {
+        shortcut: Shortcut,
+        other: Shortcut,
+    },
+    /// The shortcut can never trigger, every combination of keys that triggers it also triggers
+    /// one of the more specific shortcuts in `shadowed_by`
+    #[error("{shortcut} can never be triggered")]
+    Unreachable {
+        shortcut: Shortcut,
+        shadowed_by: Vec<Shortcut>,
+    },
+    /// The shortcut can be triggered by the same keys as the first stroke of a key sequence
+    #[error("{shortcut} overlaps with the first stroke of {sequence}")]
+    Sequence {
+        shortcut: Shortcut,
+        sequence: KeySequence,
+    },
+}
+
+impl ShortcutConflict {
+    /// Check if the shortcut is part of the conflict
+    pub fn involves(&self, shortcut: &Shortcut) -> bool {
+        match self {
+            ShortcutConflict::Duplicate(duplicate) => duplicate == shortcut,
+            ShortcutConflict::Overlap { shortcut: a, other: b } => a == shortcut || b == shortcut,
+            ShortcutConflict::Unreachable { shortcut: unreachable, shadowed_by } => {
+                unreachable == shortcut || shadowed_by.contains(shortcut)
+            }
+            ShortcutConflict::Sequence { shortcut: overlapping, .. } => overlapping == shortcut,
+        }
+    }
+}
+
+/// Find the conflicts between a list of shortcuts
+///
+/// With `most_specific_wins`, overlapping shortcuts where one is [more specific](Shortcut::is_more_specific_than)
+/// than the other aren't a conflict, since only the most specific one triggers.
+/// Instead, shortcuts that are completely shadowed by more specific shortcuts are reported as unreachable.
+///
+/// The analysis uses the default [MatchPolicy](crate::MatchPolicy), device scopes are not taken into account.
+///
+/// Example:
+///
+/// ```rust
+/// # use evdev_shortcut::{find_conflicts, Shortcut, ShortcutConflict};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let shortcuts: Vec<Shortcut> = vec!["<Ctrl>-KeyP".parse()?, "<LeftCtrl>-KeyP".parse()?];
+/// assert_eq!(1, find_conflicts(&shortcuts, false).len());
+/// assert!(find_conflicts(&shortcuts, true).is_empty());
+/// # Ok(())
+/// # }
+/// ```
+pub fn find_conflicts<'a, I>(shortcuts: I, most_specific_wins: bool) -> Vec<ShortcutConflict>
+    where I: IntoIterator<Item=&'a Shortcut>
+{
+    let mut conflicts = Vec::new();
+    let mut unique: Vec<&Shortcut> = Vec::new();
+    for shortcut in shortcuts {
+        if unique.contains(&shortcut) {
+            conflicts.push(ShortcutConflict::Duplicate(shortcut.clone()));
+        } else {
+            unique.push(shortcut);
+        }
+    }
+
+    for (index, shortcut) in unique.iter().enumerate() {
+        for other in &unique[index + 1..] {
+            if !shortcut.overlaps(other) {
+                continue;
+            }
+            let resolved = most_specific_wins
+                && (shortcut.is_more_specific_than(other) || other.is_more_specific_than(shortcut));
+            if !resolved {
+                conflicts.push(ShortcutConflict::Overlap {
+                    shortcut: (*shortcut).clone(),
+                    other: (*other).clone(),
+                });
+            }
+        }
+    }
+
+    if most_specific_wins {
+        for shortcut in &unique {
+            if let Some(shadowed_by) = shadowed_by(shortcut, &unique) {
+                conflicts.push(ShortcutConflict::Unreachable {
+                    shortcut: (*shortcut).clone(),
+                    shadowed_by,
+                });
+            }
+        }
+    }
+    conflicts
+}
+
+/// The more specific shortcuts that trigger for every combination of modifiers that triggers the shortcut
+fn shadowed_by(shortcut: &Shortcut, shortcuts: &[&Shortcut]) -> Option<Vec<Shortcut>> {
+    let mut keys: KeySet = shortcut.modifiers.custom_keys().collect();
+    keys.extend(shortcut.key());
+    let own = shortcut.key().map_or(0, Modifier::mask_from_key);
+    let mut shadowed_by = Vec::new();
+    for mask in trigger_masks(shortcut) {
+        let shadow = shortcuts.iter().find(|other| {
+            other.is_more_specific_than(shortcut) && other.is_triggered_by(&keys, mask | own)
+        })?;
+        if !shadowed_by.contains(*shadow) {
+            shadowed_by.push((*shadow).clone());
+        }
+    }
+    Some(shadowed_by)
+}
+
+/// All combinations of held modifiers that trigger the shortcut with the default policy
+fn trigger_masks(shortcut: &Shortcut) -> Vec<u16> {
+    MODIFIER_GROUPS.iter().fold(vec![0], |masks, combined| {
+        let sides = shortcut.modifiers.mask() & combined.mask();
+        let options: Vec<u16> = if sides == combined.mask() && sides.count_ones() > 1 {
+            // either side, but not both
+            vec![sides & sides.wrapping_neg(), sides & (sides - 1)]
+        } else {
+            vec![sides]
+        };
+        masks
+            .iter()
+            .flat_map(|mask| options.iter().map(move |option| mask | option))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_conflicts, trigger_masks, ShortcutConflict};
+    use crate::Shortcut;
+    use test_case::test_case;
+
+    fn shortcuts(shortcuts: &[&str]) -> Vec<Shortcut> {
+        shortcuts.iter().map(|shortcut| shortcut.parse().unwrap()).collect()
+    }
+
+    #[test_case("KeyP" => 1)]
+    #[test_case("<LeftCtrl>-KeyP" => 1)]
+    #[test_case("<Ctrl>-KeyP" => 2)]
+    #[test_case("<Ctrl><Alt><RightShift>-KeyP" => 4)]
+    fn trigger_masks_test(shortcut: &str) -> usize {
+        trigger_masks(&shortcut.parse().unwrap()).len()
+    }
+
+    #[test]
+    fn duplicate() {
+        let list = shortcuts(&["<Ctrl>-KeyP", "KeyA", "<Ctrl>-KeyP"]);
+        assert_eq!(vec![ShortcutConflict::Duplicate(list[0].clone())], find_conflicts(&list, false));
+    }
+
+    #[test_case(& ["<Ctrl>-KeyP", "<LeftCtrl>-KeyP"], false => 1; "sided and combined")]
+    #[test_case(& ["<Ctrl>-KeyP", "<LeftCtrl>-KeyP"], true => 0; "sided and combined, most specific")]
+    #[test_case(& ["<LeftCtrl>-KeyP", "<RightCtrl>-KeyP"], false => 0; "different sides")]
+    #[test_case(& ["<Ctrl>-KeyP", "<Ctrl><Shift>-KeyP"], false => 0; "extra modifier")]
+    #[test_case(& ["<Ctrl><LeftAlt>-KeyP", "<LeftCtrl><Alt>-KeyP"], true => 1; "neither more specific")]
+    fn overlap(list: &[&str], most_specific_wins: bool) -> usize {
+        find_conflicts(&shortcuts(list), most_specific_wins)
+            .iter()
+            .filter(|conflict| matches!(conflict, ShortcutConflict::Overlap { .. }))
+            .count()
+    }
+
+    #[test]
+    fn modifier_key() {
+        let list = shortcuts(&["KeyLeftCtrl", "KeyCapsLock", "<Ctrl>-KeyCapsLock"]);
+        assert!(find_conflicts(&list, true).is_empty());
+    }
+
+    #[test]
+    fn custom_modifier() {
+        let list = shortcuts(&["KeyJ", "<KeySpace>-KeyJ", "<KeyTab>-KeyJ"]);
+        assert_eq!(3, find_conflicts(&list, false).len());
+        // neither custom modifier is more specific than the other
+        assert_eq!(vec![ShortcutConflict::Overlap {
+            shortcut: list[1].clone(),
+            other: list[2].clone(),
+        }], find_conflicts(&list, true));
+    }
+
+    #[test]
+    fn unreachable_shadowed() {
+        let list = shortcuts(&["<Ctrl>-KeyP", "<LeftCtrl>-KeyP", "<RightCtrl>-KeyP"]);
+        assert!(find_conflicts(&list, false)
+            .iter()
+            .all(|conflict| matches!(conflict, ShortcutConflict::Overlap { .. })));
+        assert_eq!(vec![ShortcutConflict::Unreachable {
+            shortcut: list[0].clone(),
+            shadowed_by: list[1..].to_vec(),
+        }], find_conflicts(&list, true));
+    }
+
+    #[test]
+    fn involves() {
+        let list = shortcuts(&["<Ctrl>-KeyP", "<LeftCtrl>-KeyP", "KeyA"]);
+        let conflict = &find_conflicts(&list, false)[0];
+        assert!(conflict.involves(&list[0]));
+        assert!(conflict.involves(&list[1]));
+        assert!(!conflict.involves(&list[2]));
+    }
+}
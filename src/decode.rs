@@ -0,0 +1,68 @@
+use crate::Key;
+use evdev::{InputEvent, InputEventKind, LedType, MiscType, SwitchType, Synchronization};
+use std::convert::TryFrom;
+
+/// An evdev event, decoded by its event type
+///
+/// Event codes are only meaningful in combination with the event type,
+/// an `EV_LED` event with code `1` is a CapsLock led update, not an escape key press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DecodedEvent {
+    /// `EV_KEY` event for a known key, with the value being 0 for release, 1 for press and 2 for repeat
+    Key { key: Key, value: i32 },
+    /// `EV_KEY` event for a code that isn't a known key
+    UnknownKey { code: u16, value: i32 },
+    /// `EV_MSC` `MSC_SCAN` event containing the hardware scancode of the next key event
+    Scancode(i32),
+    /// `EV_LED` event
+    Led { led: LedType, on: bool },
+    /// `EV_SW` event
+    Switch { switch: SwitchType, on: bool },
+    /// `EV_SYN` event
+    Sync(Synchronization),
+    /// Any other event that isn't relevant for shortcuts
+    Other,
+}
+
+impl From<&InputEvent> for DecodedEvent {
+    fn from(event: &InputEvent) -> Self {
+        let value = event.value();
+        match event.kind() {
+            InputEventKind::Key(key) => match Key::try_from(key.code()) {
+                Ok(key) => DecodedEvent::Key { key, value },
+                Err(_) => DecodedEvent::UnknownKey {
+                    code: key.code(),
+                    value,
+                },
+            },
+            InputEventKind::Misc(MiscType::MSC_SCAN) => DecodedEvent::Scancode(value),
+            InputEventKind::Led(led) => DecodedEvent::Led { led, on: value != 0 },
+            InputEventKind::Switch(switch) => DecodedEvent::Switch {
+                switch,
+                on: value != 0,
+            },
+            InputEventKind::Synchronization(sync) => DecodedEvent::Sync(sync),
+            _ => DecodedEvent::Other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DecodedEvent;
+    use crate::Key;
+    use evdev::{EventType, InputEvent, LedType, SwitchType, Synchronization};
+    use test_case::test_case;
+
+    #[test_case(EventType::KEY, 1, 1 => DecodedEvent::Key { key: Key::KeyEsc, value: 1 })]
+    #[test_case(EventType::KEY, 0x2fe, 1 => DecodedEvent::UnknownKey { code: 0x2fe, value: 1 })]
+    #[test_case(EventType::LED, 1, 1 => DecodedEvent::Led { led: LedType::LED_CAPSL, on: true })]
+    #[test_case(EventType::MISC, 4, 0x70039 => DecodedEvent::Scancode(0x70039))]
+    #[test_case(EventType::MISC, 0, 1 => DecodedEvent::Other)]
+    #[test_case(EventType::SWITCH, 0, 1 => DecodedEvent::Switch { switch: SwitchType::SW_LID, on: true })]
+    #[test_case(EventType::SYNCHRONIZATION, 0, 0 => DecodedEvent::Sync(Synchronization::SYN_REPORT))]
+    #[test_case(EventType::RELATIVE, 0, 1 => DecodedEvent::Other)]
+    fn decode_test(ty: EventType, code: u16, value: i32) -> DecodedEvent {
+        DecodedEvent::from(&InputEvent::new(ty, code, value))
+    }
+}
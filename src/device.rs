@@ -0,0 +1,91 @@
+use std::path::{Path, PathBuf};
+
+/// Information about an input device
+///
+/// Can be passed directly to [ShortcutListener::listen](crate::ShortcutListener::listen).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DeviceInfo {
+    /// Path of the device node, e.g. `/dev/input/event3`
+    pub path: PathBuf,
+    /// Name reported by the device
+    pub name: Option<String>,
+    pub vendor: u16,
+    pub product: u16,
+    /// Physical location of the device, e.g. `usb-0000:00:14.0-1/input0`
+    pub phys: Option<String>,
+    /// Unique identifier of the device, e.g. the serial number or bluetooth address
+    pub uniq: Option<String>,
+}
+
+impl From<&Path> for DeviceInfo {
+    /// Info for a device of which only the path is known
+    fn from(path: &Path) -> Self {
+        DeviceInfo {
+            path: path.into(),
+            name: None,
+            vendor: 0,
+            product: 0,
+            phys: None,
+            uniq: None,
+        }
+    }
+}
+
+impl AsRef<Path> for DeviceInfo {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Selects the devices a shortcut is scoped to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DeviceSelector {
+    /// Device opened by this path, the path is compared as passed to the listener without resolving symlinks
+    Path(PathBuf),
+    /// Device reporting this name
+    Name(String),
+    /// Device reporting this vendor and product id
+    Id { vendor: u16, product: u16 },
+    /// Device reporting this unique identifier
+    Uniq(String),
+}
+
+impl DeviceSelector {
+    pub fn matches(&self, device: &DeviceInfo) -> bool {
+        match self {
+            DeviceSelector::Path(path) => &device.path == path,
+            DeviceSelector::Name(name) => device.name.as_ref() == Some(name),
+            DeviceSelector::Id { vendor, product } => device.vendor == *vendor && device.product == *product,
+            DeviceSelector::Uniq(uniq) => device.uniq.as_ref() == Some(uniq),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeviceInfo, DeviceSelector};
+    use std::path::PathBuf;
+    use test_case::test_case;
+
+    fn macro_pad() -> DeviceInfo {
+        DeviceInfo {
+            path: "/dev/input/event7".into(),
+            name: Some("Macro Pad".into()),
+            vendor: 0x1234,
+            product: 0x5678,
+            phys: None,
+            uniq: Some("pad-01".into()),
+        }
+    }
+
+    #[test_case(DeviceSelector::Path(PathBuf::from("/dev/input/event7")) => true)]
+    #[test_case(DeviceSelector::Path(PathBuf::from("/dev/input/event1")) => false)]
+    #[test_case(DeviceSelector::Name("Macro Pad".into()) => true)]
+    #[test_case(DeviceSelector::Name("Keyboard".into()) => false)]
+    #[test_case(DeviceSelector::Id { vendor: 0x1234, product: 0x5678 } => true)]
+    #[test_case(DeviceSelector::Id { vendor: 0x1234, product: 0x0001 } => false)]
+    #[test_case(DeviceSelector::Uniq("pad-01".into()) => true)]
+    fn selector_matches(selector: DeviceSelector) -> bool {
+        selector.matches(&macro_pad())
+    }
+}
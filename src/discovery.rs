@@ -0,0 +1,209 @@
+use crate::emitter::DEFAULT_NAME;
+use crate::grab::VIRTUAL_KEYBOARD_NAME;
+use crate::{DeviceInfo, Key};
+use evdev::{AttributeSet, AttributeSetRef, Device};
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use tracing::debug;
+
+const LETTER_KEYS: &[Key] = &[
+    Key::KeyA, Key::KeyB, Key::KeyC, Key::KeyD, Key::KeyE, Key::KeyF, Key::KeyG,
+    Key::KeyH, Key::KeyI, Key::KeyJ, Key::KeyK, Key::KeyL, Key::KeyM, Key::KeyN,
+    Key::KeyO, Key::KeyP, Key::KeyQ, Key::KeyR, Key::KeyS, Key::KeyT, Key::KeyU,
+    Key::KeyV, Key::KeyW, Key::KeyX, Key::KeyY, Key::KeyZ,
+];
+
+const MEDIA_KEYS: &[Key] = &[
+    Key::KeyMute,
+    Key::KeyVolumeDown,
+    Key::KeyVolumeUp,
+    Key::KeyNextSong,
+    Key::KeyPlayPause,
+    Key::KeyPreviousSong,
+    Key::KeyStopCd,
+];
+
+/// Filter for discovering input devices by their capabilities
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceFilter {
+    /// The device has all letter keys, i.e. it's a regular keyboard
+    LetterKeys,
+    /// The device has any media key, such as the volume or play/pause keys
+    MediaKeys,
+    /// The device has all the provided keys
+    Keys(Vec<Key>),
+}
+
+impl DeviceFilter {
+    /// Check if a device with the provided set of supported keys matches the filter
+    pub fn matches(&self, supported: &AttributeSetRef<evdev::Key>) -> bool {
+        let supports = |key: &Key| supported.contains(evdev::Key::new(*key as u16));
+        match self {
+            DeviceFilter::LetterKeys => LETTER_KEYS.iter().all(supports),
+            DeviceFilter::MediaKeys => MEDIA_KEYS.iter().any(supports),
+            DeviceFilter::Keys(keys) => keys.iter().all(supports),
+        }
+    }
+}
+
+/// Find all input devices matching any of the provided filters
+///
+/// All `/dev/input/event*` device nodes are inspected, devices that can't be opened are skipped.
+/// Paths that refer to the same device node are only returned once.
+/// A physical device can have multiple nodes, e.g. one for its letter keys and one for its media keys,
+/// each matching node is returned.
+///
+/// Example:
+///
+/// ```rust,no_run
+/// # use evdev_shortcut::{discover, DeviceFilter, ShortcutListener};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let listener = ShortcutListener::new();
+/// let devices = discover(&[DeviceFilter::LetterKeys, DeviceFilter::MediaKeys])?;
+/// let stream = listener.listen(&devices)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn discover(filters: &[DeviceFilter]) -> io::Result<Vec<DeviceInfo>> {
+    discover_in(Path::new("/dev/input"), filters)
+}
+
+fn discover_in(dir: &Path, filters: &[DeviceFilter]) -> io::Result<Vec<DeviceInfo>> {
+    let mut paths = Vec::new();
+    for entry in dir.read_dir()? {
+        let path = entry?.path();
+        let is_event_device = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("event"));
+        if is_event_device {
+            paths.push(path);
+        }
+    }
+    paths.sort_by_key(|path| event_number(path));
+
+    let candidates = paths.into_iter().filter_map(|path| {
+        let device = match Device::open(&path) {
+            Ok(device) => device,
+            Err(error) => {
+                debug!(device = ?path, %error, "skipping device that can't be opened");
+                return None;
+            }
+        };
+        if is_own_device(device.name()) {
+            return None;
+        }
+        let node = path.metadata().ok()?.rdev();
+        let supported = device.supported_keys()?.iter().collect();
+        Some((node, device_info(&path, &device), supported))
+    });
+
+    Ok(select_devices(candidates, filters))
+}
+
+/// Check if a device name belongs to one of the virtual keyboards created by this crate
+///
+/// Listening to those would pick up the keys that were forwarded or emitted by this crate again.
+pub(crate) fn is_own_device(name: Option<&str>) -> bool {
+    matches!(name, Some(VIRTUAL_KEYBOARD_NAME | DEFAULT_NAME))
+}
+
+/// Get the information of an opened device
+pub(crate) fn device_info(path: &Path, device: &Device) -> DeviceInfo {
+    let id = device.input_id();
+    DeviceInfo {
+        path: path.into(),
+        name: device.name().map(String::from),
+        vendor: id.vendor(),
+        product: id.product(),
+        phys: device.physical_path().map(String::from),
+        uniq: device.unique_name().map(String::from),
+    }
+}
+
+fn event_number(path: &Path) -> u32 {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_prefix("event"))
+        .and_then(|number| number.parse().ok())
+        .unwrap_or(u32::MAX)
+}
+
+/// Select the devices matching any of the filters, from (device number, info, supported keys) of each node
+///
+/// Devices are deduplicated by their device number, distinct nodes of one physical device are all kept.
+fn select_devices<I>(candidates: I, filters: &[DeviceFilter]) -> Vec<DeviceInfo>
+    where I: IntoIterator<Item=(u64, DeviceInfo, AttributeSet<evdev::Key>)>
+{
+    let mut nodes = Vec::new();
+    let mut devices = Vec::new();
+    for (node, info, supported) in candidates {
+        if filters.iter().any(|filter| filter.matches(&supported)) && !nodes.contains(&node) {
+            nodes.push(node);
+            devices.push(info);
+        }
+    }
+    devices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn key_set(keys: &[Key]) -> AttributeSet<evdev::Key> {
+        keys.iter().map(|key| evdev::Key::new(*key as u16)).collect()
+    }
+
+    fn device(path: &str, name: &str, phys: &str) -> DeviceInfo {
+        DeviceInfo {
+            path: path.into(),
+            name: Some(name.into()),
+            vendor: 0x046d,
+            product: 0xc52b,
+            phys: Some(phys.into()),
+            uniq: None,
+        }
+    }
+
+    #[test_case(DeviceFilter::LetterKeys, LETTER_KEYS => true)]
+    #[test_case(DeviceFilter::LetterKeys, &[Key::KeyA, Key::KeyVolumeUp] => false)]
+    #[test_case(DeviceFilter::MediaKeys, &[Key::KeyVolumeUp] => true)]
+    #[test_case(DeviceFilter::MediaKeys, LETTER_KEYS => false)]
+    #[test_case(DeviceFilter::Keys(vec![Key::KeyF13, Key::KeyF14]), &[Key::KeyF13, Key::KeyF14, Key::KeyF15] => true)]
+    #[test_case(DeviceFilter::Keys(vec![Key::KeyF13, Key::KeyF14]), &[Key::KeyF13] => false)]
+    fn filter_test(filter: DeviceFilter, keys: &[Key]) -> bool {
+        filter.matches(&key_set(keys))
+    }
+
+    #[test]
+    fn select_by_device_node() {
+        let letters = key_set(LETTER_KEYS);
+        let media = key_set(MEDIA_KEYS);
+        let devices = select_devices([
+            (0x0d43, device("/dev/input/event3", "Keyboard", "usb-0000:00:14.0-1/input0"), letters.clone()),
+            // a separate node of the same keyboard for its media keys, with the same identity
+            (0x0d44, device("/dev/input/event4", "Keyboard", "usb-0000:00:14.0-1/input0"), media),
+            (0x0d43, device("/dev/input/by-id/keyboard", "Keyboard", "usb-0000:00:14.0-1/input0"), letters),
+            (0x0d45, device("/dev/input/event5", "Mouse", "usb-0000:00:14.0-2/input0"), key_set(&[Key::BtnLeft])),
+        ], &[DeviceFilter::LetterKeys, DeviceFilter::MediaKeys]);
+        let paths: Vec<_> = devices.iter().map(|device| device.path.to_str().unwrap()).collect();
+        assert_eq!(vec!["/dev/input/event3", "/dev/input/event4"], paths);
+    }
+
+    #[test_case(Some("AT Translated Set 2 keyboard") => false)]
+    #[test_case(None => false)]
+    #[test_case(Some(VIRTUAL_KEYBOARD_NAME) => true)]
+    #[test_case(Some(DEFAULT_NAME) => true)]
+    fn own_device_test(name: Option<&str>) -> bool {
+        is_own_device(name)
+    }
+
+    #[test_case("/dev/input/event12" => 12)]
+    #[test_case("/dev/input/event2" => 2)]
+    #[test_case("/dev/input/mouse0" => u32::MAX)]
+    fn event_number_test(path: &str) -> u32 {
+        event_number(Path::new(path))
+    }
+}
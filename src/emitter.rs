@@ -0,0 +1,128 @@
+use crate::{Key, Shortcut};
+use evdev::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use evdev::{AttributeSet, EventType, InputEvent};
+use std::convert::TryFrom;
+use std::io;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// Name of the virtual keyboard used when no name is provided
+pub(crate) const DEFAULT_NAME: &str = "evdev-shortcut emitter";
+
+/// Virtual keyboard for sending shortcuts
+///
+/// Creating the virtual keyboard requires write access to `/dev/uinput`.
+///
+/// Example:
+///
+/// ```rust,no_run
+/// # use evdev_shortcut::{ShortcutEmitter, Shortcut, Modifier, Key};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut emitter = ShortcutEmitter::new()?;
+/// emitter.send(&Shortcut::new(&[Modifier::Ctrl], Key::KeyC))?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ShortcutEmitter {
+    device: VirtualDevice,
+}
+
+impl ShortcutEmitter {
+    /// Create a new virtual keyboard
+    pub fn new() -> io::Result<Self> {
+        ShortcutEmitter::with_name(DEFAULT_NAME)
+    }
+
+    /// Create a new virtual keyboard with the provided device name
+    pub fn with_name(name: &str) -> io::Result<Self> {
+        let keys: AttributeSet<evdev::Key> = (0..=Key::KeyMax as u16)
+            .filter(|code| Key::try_from(*code).is_ok())
+            .map(evdev::Key::new)
+            .collect();
+        let device = VirtualDeviceBuilder::new()?
+            .name(name)
+            .with_keys(&keys)?
+            .build()?;
+        Ok(ShortcutEmitter { device })
+    }
+
+    /// Emit a single key event, with the value being 0 for release, 1 for press and 2 for repeat
+    pub fn emit_key(&mut self, key: Key, value: i32) -> io::Result<()> {
+        self.device
+            .emit(&[InputEvent::new(EventType::KEY, key as u16, value)])
+    }
+
+    /// Press the modifiers and key of a shortcut, without releasing them
+    pub fn press(&mut self, shortcut: &Shortcut) -> io::Result<()> {
+        for key in shortcut_keys(shortcut) {
+            self.emit_key(key, 1)?;
+        }
+        Ok(())
+    }
+
+    /// Release the key and modifiers of a shortcut, in the reverse order they were pressed in
+    pub fn release(&mut self, shortcut: &Shortcut) -> io::Result<()> {
+        let keys: Vec<_> = shortcut_keys(shortcut).collect();
+        for key in keys.into_iter().rev() {
+            self.emit_key(key, 0)?;
+        }
+        Ok(())
+    }
+
+    /// Press and release a shortcut
+    pub fn send(&mut self, shortcut: &Shortcut) -> io::Result<()> {
+        self.press(shortcut)?;
+        self.release(shortcut)
+    }
+
+    /// Send a sequence of shortcuts, waiting `delay` between each key event
+    ///
+    /// Some applications miss key events that are sent too quickly after each other,
+    /// a delay of a few milliseconds is usually enough to prevent this.
+    pub async fn play(&mut self, shortcuts: &[Shortcut], delay: Duration) -> io::Result<()> {
+        for shortcut in shortcuts {
+            for (key, value) in shortcut_events(shortcut) {
+                self.emit_key(key, value)?;
+                sleep(delay).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The keys to press for a shortcut, modifiers first
+fn shortcut_keys(shortcut: &Shortcut) -> impl Iterator<Item=Key> + '_ {
+    shortcut
+        .modifiers
+        .modifiers()
+        .map(|modifier| modifier.key())
+        .chain(shortcut.modifiers.custom_keys())
+        .chain(shortcut.key())
+}
+
+/// The key events for pressing and releasing a shortcut
+fn shortcut_events(shortcut: &Shortcut) -> Vec<(Key, i32)> {
+    let keys: Vec<_> = shortcut_keys(shortcut).collect();
+    let presses = keys.iter().map(|key| (*key, 1));
+    let releases = keys.iter().rev().map(|key| (*key, 0));
+    presses.chain(releases).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::shortcut_events;
+    use crate::Key;
+    use test_case::test_case;
+
+    #[test_case("KeyA" => vec![(Key::KeyA, 1), (Key::KeyA, 0)])]
+    #[test_case("<Ctrl>-KeyC" => vec![(Key::KeyLeftCtrl, 1), (Key::KeyC, 1), (Key::KeyC, 0), (Key::KeyLeftCtrl, 0)])]
+    #[test_case("<RightAlt><Shift>-KeyF4" => vec![
+        (Key::KeyRightAlt, 1), (Key::KeyLeftShift, 1), (Key::KeyF4, 1),
+        (Key::KeyF4, 0), (Key::KeyLeftShift, 0), (Key::KeyRightAlt, 0),
+    ])]
+    #[test_case("<Ctrl><Shift>" => vec![(Key::KeyLeftCtrl, 1), (Key::KeyLeftShift, 1), (Key::KeyLeftShift, 0), (Key::KeyLeftCtrl, 0)])]
+    #[test_case("<KeySpace>-KeyJ" => vec![(Key::KeySpace, 1), (Key::KeyJ, 1), (Key::KeyJ, 0), (Key::KeySpace, 0)])]
+    fn shortcut_events_test(shortcut: &str) -> Vec<(Key, i32)> {
+        shortcut_events(&shortcut.parse().unwrap())
+    }
+}
@@ -0,0 +1,493 @@
+use crate::listener::SourceEvent;
+use crate::{DeviceInfo, Key, KeySet, MatchEvent, Modifier, Shortcut, ShortcutMatcher, ShortcutState};
+use async_stream::stream;
+use evdev::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use evdev::{AttributeSet, Device, EventStream, EventType, InputEvent, MiscType};
+use futures::{Stream, StreamExt};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tracing::{debug, warn};
+
+/// Name of the virtual keyboard that non-shortcut keys are forwarded to
+pub(crate) const VIRTUAL_KEYBOARD_NAME: &str = "evdev-shortcut virtual keyboard";
+
+/// Key that is tapped to interrupt forwarded modifiers when a shortcut key is swallowed
+///
+/// This prevents the focused application from seeing a lone modifier tap,
+/// which many desktops bind to an action of their own.
+const INTERRUPT_KEY: Key = Key::KeyUnknown;
+
+/// Event types that can be forwarded through the virtual keyboard
+///
+/// Led and repeat events only report the state of the device, they don't need to be forwarded.
+const FORWARDED_EVENT_TYPES: [EventType; 5] = [
+    EventType::SYNCHRONIZATION,
+    EventType::KEY,
+    EventType::MISC,
+    EventType::LED,
+    EventType::REPEAT,
+];
+
+/// Output for forwarded events
+pub(crate) trait KeySink {
+    /// Emit the events as a single frame
+    fn emit(&mut self, events: &[InputEvent]) -> io::Result<()>;
+}
+
+impl KeySink for VirtualDevice {
+    fn emit(&mut self, events: &[InputEvent]) -> io::Result<()> {
+        VirtualDevice::emit(self, events)
+    }
+}
+
+/// Check if all events of a device can be forwarded while it is grabbed
+///
+/// Devices that also report e.g. pointer movement or switches are not grabbed,
+/// those events would be lost since the virtual keyboard only forwards key events.
+pub(crate) fn can_grab(device: &Device) -> bool {
+    device
+        .supported_events()
+        .iter()
+        .all(|event_type| FORWARDED_EVENT_TYPES.contains(&event_type))
+        && device
+            .misc_properties()
+            .is_none_or(|misc| misc.iter().all(|misc| misc == MiscType::MSC_SCAN))
+}
+
+/// Forwarding state for listening to grabbed devices
+///
+/// All keys that aren't part of a shortcut are forwarded to a virtual keyboard,
+/// the keys that trigger a shortcut are swallowed.
+pub(crate) struct Grab<S = VirtualDevice> {
+    sink: S,
+    /// Keys that are held down on the virtual keyboard
+    forwarded: KeySet,
+    /// Codes without a known key that are held down on the virtual keyboard
+    forwarded_unknown: Vec<u16>,
+    /// Keys of which the press was swallowed, their repeats and release are swallowed too
+    swallowed: KeySet,
+    /// Scancode that was reported for the next key event of a device
+    scancode: Option<(Arc<DeviceInfo>, i32)>,
+    /// Swallowed strokes of the key sequence that is being typed, with the modifiers that were held for them
+    strokes: Vec<(Key, KeySet)>,
+    release_matcher: ShortcutMatcher,
+    released: Arc<AtomicBool>,
+}
+
+impl Grab {
+    pub fn new(release: Shortcut) -> io::Result<Self> {
+        // all key codes are supported, unknown keys are forwarded too
+        let keys: AttributeSet<evdev::Key> = (0..=Key::KeyMax as u16).map(evdev::Key::new).collect();
+        let mut misc = AttributeSet::new();
+        misc.insert(MiscType::MSC_SCAN);
+        let device = VirtualDeviceBuilder::new()?
+            .name(VIRTUAL_KEYBOARD_NAME)
+            .with_keys(&keys)?
+            .with_msc(&misc)?
+            .build()?;
+        Ok(Grab::with_sink(device, release))
+    }
+}
+
+impl<S: KeySink> Grab<S> {
+    pub fn with_sink(sink: S, release: Shortcut) -> Self {
+        let mut release_matcher = ShortcutMatcher::new();
+        release_matcher.add(release);
+
+        Grab {
+            sink,
+            forwarded: KeySet::new(),
+            forwarded_unknown: Vec::new(),
+            swallowed: KeySet::new(),
+            scancode: None,
+            strokes: Vec::new(),
+            release_matcher,
+            released: Arc::default(),
+        }
+    }
+
+    /// Flag that is set once the grab has been released by the emergency combo
+    pub fn released(&self) -> &Arc<AtomicBool> {
+        &self.released
+    }
+
+    /// Remember the scancode of a device, it is forwarded together with the next key event of that device
+    pub fn handle_scancode(&mut self, device: &Arc<DeviceInfo>, scancode: i32, grabbed: bool) {
+        self.scancode = grabbed.then(|| (device.clone(), scancode));
+    }
+
+    /// Forward or swallow a key event
+    ///
+    /// `grabbed` is whether the device was grabbed when the event was read,
+    /// `consumed` is whether the event triggered a shortcut with this key or continued a key sequence.
+    ///
+    /// Returns `true` if the emergency release combo was pressed.
+    pub fn handle_key(&mut self, device: &Arc<DeviceInfo>, key: Key, value: i32, time: SystemTime, grabbed: bool, consumed: bool) -> bool {
+        let scancode = self.take_scancode(device);
+        let release_pressed = self
+            .release_matcher
+            .handle_key(key, value, time)
+            .any(|event| matches!(event, MatchEvent::Shortcut(event) if event.state == ShortcutState::Pressed));
+        let triggered_release = release_pressed && !self.released.swap(true, Ordering::Relaxed);
+        if triggered_release {
+            warn!("emergency release combo pressed, releasing grabbed devices");
+        }
+
+        let forward = match value {
+            _ if !grabbed => {
+                // the device has been released, but keys that are held on the virtual keyboard
+                // still need to be released there
+                value == 0 && self.forwarded.contains(key)
+            }
+            1 if consumed => {
+                self.swallowed.insert(key);
+                if !self.held_modifiers().is_empty() {
+                    self.emit(INTERRUPT_KEY as u16, 1, None);
+                    self.emit(INTERRUPT_KEY as u16, 0, None);
+                }
+                false
+            }
+            0 => !self.swallowed.remove(key),
+            _ => !self.swallowed.contains(key),
+        };
+
+        if forward {
+            match value {
+                1 => self.forwarded.insert(key),
+                0 => self.forwarded.remove(key),
+                _ => false,
+            };
+            self.emit(key as u16, value, scancode);
+        }
+
+        triggered_release
+    }
+
+    /// Forward an event for a key code that isn't a known key
+    ///
+    /// Unknown keys can't be part of a shortcut, so they are never swallowed.
+    pub fn handle_unknown_key(&mut self, device: &Arc<DeviceInfo>, code: u16, value: i32, grabbed: bool) {
+        let scancode = self.take_scancode(device);
+        let held = self.forwarded_unknown.iter().position(|held| *held == code);
+        let forward = match (value, held) {
+            (0, Some(index)) => {
+                self.forwarded_unknown.swap_remove(index);
+                true
+            }
+            (1, None) if grabbed => {
+                self.forwarded_unknown.push(code);
+                true
+            }
+            _ => grabbed,
+        };
+        if forward {
+            self.emit(code, value, scancode);
+        }
+    }
+
+    /// Release keys that are held on the virtual keyboard and swallow the rest of their events
+    ///
+    /// Used when a chord completes, to take back the chord keys that were forwarded before it was complete.
+    pub fn swallow_held(&mut self, keys: &[Key]) {
+        for key in keys {
+            if self.forwarded.remove(*key) {
+                self.emit(*key as u16, 0, None);
+                self.swallowed.insert(*key);
+            }
+        }
+    }
+
+    /// Remember a swallowed key that continued a key sequence, to replay it if the sequence is aborted
+    pub fn buffer_stroke(&mut self, key: Key) {
+        if self.swallowed.contains(key) {
+            let modifiers = self.held_modifiers();
+            self.strokes.push((key, modifiers));
+        }
+    }
+
+    /// Forward the swallowed strokes of an aborted key sequence
+    ///
+    /// Each stroke is replayed with the modifiers that were held when it was typed,
+    /// after which the modifiers on the virtual keyboard are restored.
+    pub fn replay_strokes(&mut self) {
+        for (key, modifiers) in std::mem::take(&mut self.strokes) {
+            let held = self.held_modifiers();
+            let lift: Vec<_> = held.iter().filter(|modifier| !modifiers.contains(*modifier)).collect();
+            let press: Vec<_> = modifiers.iter().filter(|modifier| !held.contains(*modifier)).collect();
+            for modifier in &lift {
+                self.emit(*modifier as u16, 0, None);
+            }
+            for modifier in &press {
+                self.emit(*modifier as u16, 1, None);
+            }
+            self.emit(key as u16, 1, None);
+            self.emit(key as u16, 0, None);
+            for modifier in press.iter().rev() {
+                self.emit(*modifier as u16, 0, None);
+            }
+            for modifier in &lift {
+                self.emit(*modifier as u16, 1, None);
+            }
+        }
+    }
+
+    /// Forget the swallowed strokes of a completed key sequence
+    pub fn clear_strokes(&mut self) {
+        self.strokes.clear();
+    }
+
+    fn held_modifiers(&self) -> KeySet {
+        self.forwarded.iter().filter(|key| Modifier::mask_from_key(*key) != 0).collect()
+    }
+
+    fn take_scancode(&mut self, device: &Arc<DeviceInfo>) -> Option<i32> {
+        match self.scancode.take() {
+            Some((scancode_device, scancode)) if Arc::ptr_eq(&scancode_device, device) => Some(scancode),
+            _ => None,
+        }
+    }
+
+    fn emit(&mut self, code: u16, value: i32, scancode: Option<i32>) {
+        let key = InputEvent::new(EventType::KEY, code, value);
+        let result = match scancode {
+            Some(scancode) => self.sink.emit(&[InputEvent::new(EventType::MISC, MiscType::MSC_SCAN.0, scancode), key]),
+            None => self.sink.emit(&[key]),
+        };
+        if let Err(error) = result {
+            warn!(code, %error, "failed to forward key to virtual keyboard");
+        }
+    }
+}
+
+/// Events of a grabbed device, the device is released after the first event once `released` is set
+///
+/// If the device isn't `grabbed` yet, it is grabbed once none of its keys are held anymore,
+/// grabbing it while keys are held would leave those keys stuck for other applications.
+pub(crate) fn grabbed_device_events(device: Arc<DeviceInfo>, mut events: EventStream, released: Arc<AtomicBool>, mut grabbed: bool) -> impl Stream<Item=SourceEvent> {
+    stream! {
+        let mut grab_pending = !grabbed;
+        while let Some(event) = events.next().await {
+            let failed = event.is_err();
+            let key_released = matches!(&event, Ok(event) if event.event_type() == EventType::KEY && event.value() == 0);
+            yield SourceEvent::Input { device: device.clone(), event, grabbed };
+            if failed {
+                break;
+            }
+            if released.load(Ordering::Relaxed) {
+                grab_pending = false;
+                if grabbed {
+                    if let Err(error) = events.device_mut().ungrab() {
+                        warn!(device = ?device.path, %error, "failed to release grabbed device");
+                    }
+                    grabbed = false;
+                }
+            } else if grab_pending && key_released {
+                match events.device().get_key_state() {
+                    Ok(keys) if keys.iter().next().is_none() => {
+                        grab_pending = false;
+                        match events.device_mut().grab() {
+                            Ok(()) => {
+                                debug!(device = ?device.path, "grabbed device after its keys were released");
+                                grabbed = true;
+                            }
+                            Err(error) => warn!(device = ?device.path, %error, "failed to grab device"),
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(error) => warn!(device = ?device.path, %error, "failed to read held keys"),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Grab, KeySink, INTERRUPT_KEY};
+    use crate::{DeviceInfo, Key, Shortcut};
+    use evdev::{EventType, InputEvent};
+    use std::io;
+    use std::path::Path;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::time::SystemTime;
+
+    /// Forwarded frames, as (type, code, value) of each event
+    type Frames = Vec<Vec<(EventType, u16, i32)>>;
+
+    impl KeySink for Frames {
+        fn emit(&mut self, events: &[InputEvent]) -> io::Result<()> {
+            self.push(events.iter().map(|event| (event.event_type(), event.code(), event.value())).collect());
+            Ok(())
+        }
+    }
+
+    fn grab() -> Grab<Frames> {
+        Grab::with_sink(Vec::new(), "<LeftAlt><LeftCtrl><LeftShift>-KeyEsc".parse().unwrap())
+    }
+
+    fn device(path: &str) -> Arc<DeviceInfo> {
+        Arc::new(DeviceInfo::from(Path::new(path)))
+    }
+
+    /// Feed keys as (key, value, consumed)
+    fn feed(grab: &mut Grab<Frames>, keys: &[(Key, i32, bool)], grabbed: bool) -> bool {
+        let device = device("/dev/input/event0");
+        keys.iter().fold(false, |released, (key, value, consumed)| {
+            grab.handle_key(&device, *key, *value, SystemTime::now(), grabbed, *consumed) || released
+        })
+    }
+
+    /// Forwarded key events, as (key, value)
+    fn forwarded(grab: &Grab<Frames>) -> Vec<(Key, i32)> {
+        grab.sink
+            .iter()
+            .flatten()
+            .filter(|(event_type, _, _)| *event_type == EventType::KEY)
+            .map(|(_, code, value)| (Key::try_from(*code).unwrap(), *value))
+            .collect()
+    }
+
+    #[test]
+    fn forward_regular_keys() {
+        let mut grab = grab();
+        feed(&mut grab, &[(Key::KeyA, 1, false), (Key::KeyA, 2, false), (Key::KeyA, 0, false)], true);
+        assert_eq!(vec![(Key::KeyA, 1), (Key::KeyA, 2), (Key::KeyA, 0)], forwarded(&grab));
+    }
+
+    #[test]
+    fn swallow_shortcut_key() {
+        let mut grab = grab();
+        feed(&mut grab, &[
+            (Key::KeyLeftMeta, 1, false),
+            (Key::KeyN, 1, true),
+            (Key::KeyN, 2, false),
+            (Key::KeyN, 0, false),
+            (Key::KeyLeftMeta, 0, false),
+        ], true);
+        assert_eq!(vec![
+            (Key::KeyLeftMeta, 1),
+            (INTERRUPT_KEY, 1),
+            (INTERRUPT_KEY, 0),
+            (Key::KeyLeftMeta, 0),
+        ], forwarded(&grab));
+    }
+
+    #[test]
+    fn no_interrupt_without_modifiers() {
+        let mut grab = grab();
+        feed(&mut grab, &[(Key::KeyF13, 1, true), (Key::KeyF13, 0, false)], true);
+        assert!(grab.sink.is_empty());
+    }
+
+    #[test]
+    fn swallow_held_chord_keys() {
+        let mut grab = grab();
+        feed(&mut grab, &[(Key::KeyJ, 1, false)], true);
+        grab.swallow_held(&[Key::KeyJ, Key::KeyK]);
+        feed(&mut grab, &[(Key::KeyK, 1, true), (Key::KeyJ, 2, false), (Key::KeyJ, 0, false), (Key::KeyK, 0, false)], true);
+        assert_eq!(vec![(Key::KeyJ, 1), (Key::KeyJ, 0)], forwarded(&grab));
+    }
+
+    #[test]
+    fn emergency_release() {
+        let mut grab = grab();
+        assert!(!feed(&mut grab, &[(Key::KeyA, 1, false), (Key::KeyLeftCtrl, 1, false), (Key::KeyLeftAlt, 1, false), (Key::KeyLeftShift, 1, false)], true));
+        assert!(feed(&mut grab, &[(Key::KeyEsc, 1, false)], true));
+        assert!(grab.released().load(Ordering::Relaxed));
+        grab.sink.clear();
+
+        // releases of keys held on the virtual keyboard are still forwarded, nothing else is
+        assert!(!feed(&mut grab, &[
+            (Key::KeyEsc, 0, false),
+            (Key::KeyB, 1, false),
+            (Key::KeyB, 0, false),
+            (Key::KeyA, 0, false),
+        ], false));
+        assert_eq!(vec![(Key::KeyEsc, 0), (Key::KeyA, 0)], forwarded(&grab));
+    }
+
+    #[test]
+    fn emergency_release_only_triggers_once() {
+        let mut grab = grab();
+        let combo: Shortcut = "<LeftAlt><LeftCtrl><LeftShift>-KeyEsc".parse().unwrap();
+        let key = combo.key().unwrap();
+        let press = [(Key::KeyLeftCtrl, 1, false), (Key::KeyLeftAlt, 1, false), (Key::KeyLeftShift, 1, false), (key, 1, false), (key, 0, false)];
+        assert!(feed(&mut grab, &press, true));
+        assert!(!feed(&mut grab, &press, false));
+    }
+
+    #[test]
+    fn replay_aborted_sequence() {
+        let mut grab = grab();
+        feed(&mut grab, &[(Key::KeyLeftCtrl, 1, false), (Key::KeyX, 1, true)], true);
+        grab.buffer_stroke(Key::KeyX);
+        feed(&mut grab, &[(Key::KeyX, 0, false), (Key::KeyLeftCtrl, 0, false), (Key::KeySpace, 1, true)], true);
+        grab.buffer_stroke(Key::KeySpace);
+        grab.sink.clear();
+
+        grab.replay_strokes();
+        feed(&mut grab, &[(Key::KeyB, 1, false)], true);
+        assert_eq!(vec![
+            (Key::KeyLeftCtrl, 1),
+            (Key::KeyX, 1),
+            (Key::KeyX, 0),
+            (Key::KeyLeftCtrl, 0),
+            (Key::KeySpace, 1),
+            (Key::KeySpace, 0),
+            (Key::KeyB, 1),
+        ], forwarded(&grab));
+
+        // the release of a replayed key is still swallowed
+        grab.sink.clear();
+        feed(&mut grab, &[(Key::KeySpace, 0, false)], true);
+        assert!(grab.sink.is_empty());
+    }
+
+    #[test]
+    fn completed_sequence_is_not_replayed() {
+        let mut grab = grab();
+        feed(&mut grab, &[(Key::KeySpace, 1, true)], true);
+        grab.buffer_stroke(Key::KeySpace);
+        feed(&mut grab, &[(Key::KeyF, 1, true)], true);
+        grab.clear_strokes();
+        grab.replay_strokes();
+        assert!(grab.sink.is_empty());
+    }
+
+    #[test]
+    fn forward_unknown_keys() {
+        let mut grab = grab();
+        let device = device("/dev/input/event0");
+        grab.handle_unknown_key(&device, 0x2fe, 1, true);
+        grab.handle_unknown_key(&device, 0x2fe, 2, true);
+        grab.handle_unknown_key(&device, 0x2fd, 1, false);
+        grab.handle_unknown_key(&device, 0x2fd, 0, false);
+        grab.handle_unknown_key(&device, 0x2fe, 0, false);
+        assert_eq!(vec![
+            vec![(EventType::KEY, 0x2fe, 1)],
+            vec![(EventType::KEY, 0x2fe, 2)],
+            vec![(EventType::KEY, 0x2fe, 0)],
+        ], grab.sink);
+    }
+
+    #[test]
+    fn forward_scancode_with_key() {
+        let mut grab = grab();
+        let keyboard = device("/dev/input/event0");
+        let other = device("/dev/input/event1");
+        let now = SystemTime::now();
+        grab.handle_scancode(&keyboard, 0x70004, true);
+        grab.handle_key(&keyboard, Key::KeyA, 1, now, true, false);
+        grab.handle_scancode(&keyboard, 0x70004, true);
+        grab.handle_key(&other, Key::KeyA, 0, now, true, false);
+        grab.handle_scancode(&keyboard, 0x70005, true);
+        grab.handle_key(&keyboard, Key::KeyB, 1, now, true, true);
+        assert_eq!(vec![
+            vec![(EventType::MISC, 4, 0x70004), (EventType::KEY, Key::KeyA as u16, 1)],
+            vec![(EventType::KEY, Key::KeyA as u16, 0)],
+        ], grab.sink);
+    }
+}
@@ -0,0 +1,118 @@
+use crate::Key;
+use std::convert::TryFrom;
+use std::fmt::{self, Debug, Formatter};
+use std::iter::FromIterator;
+
+const WORDS: usize = (Key::KeyMax as usize + 1) / 64;
+
+/// Fixed size set of keys, backed by a bitset covering all key codes
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct KeySet([u64; WORDS]);
+
+impl KeySet {
+    pub fn new() -> Self {
+        KeySet::default()
+    }
+
+    fn position(key: Key) -> (usize, u64) {
+        let code = key as usize;
+        (code / 64, 1 << (code % 64))
+    }
+
+    /// Returns `true` if the key was not previously in the set
+    pub fn insert(&mut self, key: Key) -> bool {
+        let (word, bit) = KeySet::position(key);
+        let inserted = self.0[word] & bit == 0;
+        self.0[word] |= bit;
+        inserted
+    }
+
+    /// Returns `true` if the key was previously in the set
+    pub fn remove(&mut self, key: Key) -> bool {
+        let (word, bit) = KeySet::position(key);
+        let removed = self.0[word] & bit != 0;
+        self.0[word] &= !bit;
+        removed
+    }
+
+    pub fn contains(&self, key: Key) -> bool {
+        let (word, bit) = KeySet::position(key);
+        self.0[word] & bit != 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|word| *word == 0)
+    }
+
+    pub fn clear(&mut self) {
+        self.0 = [0; WORDS];
+    }
+
+    /// Iterate over the keys in the set, ordered by key code
+    pub fn iter(&self) -> impl Iterator<Item=Key> + '_ {
+        self.0.iter().enumerate().flat_map(|(index, word)| {
+            let mut word = *word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros();
+                word &= word - 1;
+                Some(index as u16 * 64 + bit as u16)
+            })
+        }).filter_map(|code| Key::try_from(code).ok())
+    }
+}
+
+impl Debug for KeySet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl FromIterator<Key> for KeySet {
+    fn from_iter<T: IntoIterator<Item=Key>>(iter: T) -> Self {
+        let mut set = KeySet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<Key> for KeySet {
+    fn extend<T: IntoIterator<Item=Key>>(&mut self, iter: T) {
+        for key in iter {
+            self.insert(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KeySet;
+    use crate::Key;
+
+    #[test]
+    fn insert_remove() {
+        let mut set = KeySet::new();
+        assert!(set.is_empty());
+        assert!(set.insert(Key::KeyA));
+        assert!(!set.insert(Key::KeyA));
+        assert!(set.insert(Key::KeyMax));
+        assert!(set.contains(Key::KeyA));
+        assert!(!set.contains(Key::KeyB));
+        assert_eq!(2, set.len());
+        assert!(set.remove(Key::KeyA));
+        assert!(!set.remove(Key::KeyA));
+        assert_eq!(vec![Key::KeyMax], set.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn iter_ordered() {
+        let set: KeySet = [Key::KeyMax, Key::KeyLeftCtrl, Key::KeyReserved, Key::KeyF24].into_iter().collect();
+        assert_eq!(vec![Key::KeyReserved, Key::KeyLeftCtrl, Key::KeyF24, Key::KeyMax], set.iter().collect::<Vec<_>>());
+    }
+}
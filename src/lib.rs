@@ -9,9 +9,7 @@
 //! Example:
 //!
 //! ```rust,no_run
-//! # use std::path::PathBuf;
-//! # use glob::GlobError;
-//! # use evdev_shortcut::{ShortcutListener, Shortcut, Modifier, Key};
+//! # use evdev_shortcut::{discover, DeviceFilter, ShortcutListener, Shortcut, Modifier, Key};
 //! # use tokio::pin;
 //! # use futures::stream::StreamExt;
 //! # #[tokio::main]
@@ -19,8 +17,7 @@
 //! let listener = ShortcutListener::new();
 //! listener.add(Shortcut::new(&[Modifier::Meta], Key::KeyN));
 //!
-//! let devices =
-//!     glob::glob("/dev/input/by-id/*-kbd")?.collect::<Result<Vec<PathBuf>, GlobError>>()?;
+//! let devices = discover(&[DeviceFilter::LetterKeys, DeviceFilter::MediaKeys])?;
 //!
 //! let stream = listener.listen(&devices)?;
 //! pin!(stream);
@@ -32,21 +29,51 @@
 //! # }
 //! ```
 
+pub use chord::{Chord, ChordConflict, ChordEvent, InvalidChord};
+pub use conflict::{find_conflicts, ShortcutConflict};
+pub use device::{DeviceInfo, DeviceSelector};
 pub use keycodes::Key;
+pub use keyset::KeySet;
+pub use matcher::{MatchEvent, ShortcutMatcher, ShortcutSet};
+pub use sequence::{KeySequence, SequenceConflict, SequenceEvent, SequenceState};
+pub use tap::{Tap, TapEvent, TapKey};
 use parse_display::{Display, FromStr, ParseError};
 use std::collections::HashSet;
 use std::fmt::{self, Debug, Display, Formatter};
+use std::io;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use thiserror::Error;
 
+mod chord;
+mod conflict;
+mod device;
 mod keycodes;
+mod keyset;
+mod matcher;
+mod sequence;
+mod tap;
+
+#[cfg(feature = "listener")]
+mod decode;
+#[cfg(feature = "emitter")]
+mod emitter;
+#[cfg(feature = "listener")]
+mod discovery;
+#[cfg(feature = "listener")]
+mod grab;
 
 #[cfg(feature = "listener")]
 mod listener;
 
 #[cfg(feature = "listener")]
-pub use listener::ShortcutListener;
+pub use discovery::{discover, DeviceFilter};
+#[cfg(feature = "emitter")]
+pub use emitter::ShortcutEmitter;
+#[cfg(feature = "listener")]
+pub use listener::{ListenerEvent, ShortcutListener};
 
 /// Error emitted when an input device can't be opened
 #[derive(Debug, Clone, Error)]
@@ -55,13 +82,25 @@ pub struct DeviceOpenError {
     pub device: PathBuf,
 }
 
+/// Error emitted when reading from an input device fails
+#[derive(Debug, Clone, Error)]
+#[error("Failed to read from device {device:?}: {error}")]
+pub struct DeviceReadError {
+    pub device: PathBuf,
+    #[source]
+    pub error: Arc<io::Error>,
+}
+
 /// Modifier key for shortcuts
+///
+/// `AltGr` is the right alt key, it behaves the same as `RightAlt`.
 #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq, Display, FromStr)]
 #[repr(u8)]
 pub enum Modifier {
     Alt,
     LeftAlt,
     RightAlt,
+    AltGr,
     Ctrl,
     LeftCtrl,
     RightCtrl,
@@ -71,8 +110,11 @@ pub enum Modifier {
     Meta,
     LeftMeta,
     RightMeta,
+    CapsLock,
+    Fn,
 }
 
+/// Modifiers in display order, aliases are left out
 const ALL_MODIFIERS: &[Modifier] = &[
     Modifier::Alt,
     Modifier::LeftAlt,
@@ -86,21 +128,26 @@ const ALL_MODIFIERS: &[Modifier] = &[
     Modifier::Meta,
     Modifier::LeftMeta,
     Modifier::RightMeta,
+    Modifier::CapsLock,
+    Modifier::Fn,
 ];
 
-const COMBINED_MODIFIERS: &[Modifier] = &[
+/// Modifiers that cover every side of a modifier key, each modifier key belongs to exactly one of these groups
+const MODIFIER_GROUPS: &[Modifier] = &[
     Modifier::Alt,
     Modifier::Ctrl,
     Modifier::Shift,
     Modifier::Meta,
+    Modifier::CapsLock,
+    Modifier::Fn,
 ];
 
 impl Modifier {
-    pub fn mask(&self) -> u8 {
+    pub fn mask(&self) -> u16 {
         match self {
             Modifier::Alt => 0b00000011,
             Modifier::LeftAlt => 0b00000001,
-            Modifier::RightAlt => 0b00000010,
+            Modifier::RightAlt | Modifier::AltGr => 0b00000010,
             Modifier::Ctrl => 0b00001100,
             Modifier::LeftCtrl => 0b00000100,
             Modifier::RightCtrl => 0b00001000,
@@ -110,10 +157,12 @@ impl Modifier {
             Modifier::Shift => 0b11000000,
             Modifier::LeftShift => 0b01000000,
             Modifier::RightShift => 0b10000000,
+            Modifier::CapsLock => 0b01_00000000,
+            Modifier::Fn => 0b10_00000000,
         }
     }
 
-    pub fn mask_from_key(key: Key) -> u8 {
+    pub fn mask_from_key(key: Key) -> u16 {
         match key {
             Key::KeyLeftAlt => 0b00000001,
             Key::KeyRightAlt => 0b00000010,
@@ -123,30 +172,103 @@ impl Modifier {
             Key::KeyRightMeta => 0b00100000,
             Key::KeyLeftShift => 0b01000000,
             Key::KeyRightShift => 0b10000000,
+            Key::KeyCapsLock => 0b01_00000000,
+            Key::KeyFn => 0b10_00000000,
             _ => 0,
         }
     }
+
+    /// The key to press for this modifier, combined modifiers use the left key
+    pub fn key(&self) -> Key {
+        match self {
+            Modifier::Alt | Modifier::LeftAlt => Key::KeyLeftAlt,
+            Modifier::RightAlt | Modifier::AltGr => Key::KeyRightAlt,
+            Modifier::Ctrl | Modifier::LeftCtrl => Key::KeyLeftCtrl,
+            Modifier::RightCtrl => Key::KeyRightCtrl,
+            Modifier::Meta | Modifier::LeftMeta => Key::KeyLeftMeta,
+            Modifier::RightMeta => Key::KeyRightMeta,
+            Modifier::Shift | Modifier::LeftShift => Key::KeyLeftShift,
+            Modifier::RightShift => Key::KeyRightShift,
+            Modifier::CapsLock => Key::KeyCapsLock,
+            Modifier::Fn => Key::KeyFn,
+        }
+    }
 }
 
-/// Set of modifier keys for shortcuts
+/// Set of modifiers for shortcuts
+///
+/// Besides the [built-in modifiers](Modifier), any key can be used as a custom modifier by writing it as a modifier, like `<KeySpace>`.
+/// A custom modifier has to be held for the shortcut to trigger, but unlike the built-in modifiers,
+/// holding it doesn't stop shortcuts without it from triggering.
+///
+/// `<Hyper>` is parsed as `<Alt><Ctrl><Shift><Meta>`, the combination that key remappers commonly send for a hyper key.
+///
+/// A list can hold up to [MAX_CUSTOM_MODIFIERS](ModifierList::MAX_CUSTOM_MODIFIERS) custom modifiers.
 #[derive(Clone, Debug, Hash, PartialEq, Eq, Copy, Default)]
-pub struct ModifierList(u8);
+pub struct ModifierList {
+    mask: u16,
+    /// Keys that are used as custom modifiers, sorted by key code
+    keys: [Option<Key>; ModifierList::MAX_CUSTOM_MODIFIERS],
+}
 
 impl ModifierList {
+    /// Maximum number of custom modifiers in a list
+    pub const MAX_CUSTOM_MODIFIERS: usize = 4;
+
     pub fn new(modifiers: &[Modifier]) -> Self {
-        ModifierList(modifiers
-            .iter()
-            .fold(0, |mask, modifier| mask | modifier.mask()))
+        ModifierList {
+            mask: modifiers
+                .iter()
+                .fold(0, |mask, modifier| mask | modifier.mask()),
+            keys: [None; ModifierList::MAX_CUSTOM_MODIFIERS],
+        }
+    }
+
+    /// The `<Hyper>` modifier, `<Alt><Ctrl><Shift><Meta>`
+    pub fn hyper() -> Self {
+        ModifierList::new(&[Modifier::Alt, Modifier::Ctrl, Modifier::Shift, Modifier::Meta])
+    }
+
+    /// Add a key as modifier, the keys of built-in modifiers are added as that modifier
+    ///
+    /// Returns `false` if the list already holds the maximum number of custom modifiers.
+    pub fn insert_key(&mut self, key: Key) -> bool {
+        let mask = Modifier::mask_from_key(key);
+        if mask != 0 {
+            self.mask |= mask;
+            return true;
+        }
+        if self.has_custom_key(key) {
+            return true;
+        }
+        let Some(free) = self.keys.iter().position(Option::is_none) else {
+            return false;
+        };
+        self.keys[free] = Some(key);
+        self.keys[..=free].sort_by_key(|key| key.map(|key| key as u16));
+        true
+    }
+
+    /// Mask of the built-in modifiers
+    pub fn mask(&self) -> u16 {
+        self.mask
     }
 
-    pub fn mask(&self) -> u8 {
-        self.0
+    /// Keys that are used as custom modifiers
+    pub fn custom_keys(&self) -> impl Iterator<Item=Key> + '_ {
+        self.keys.iter().flatten().copied()
     }
 
+    /// Check if the key is used as custom modifier
+    pub fn has_custom_key(&self, key: Key) -> bool {
+        self.keys.contains(&Some(key))
+    }
+
+    /// The built-in modifiers in the list
     pub fn modifiers(&self) -> impl Iterator<Item=Modifier> {
         let mask = self.mask();
         ALL_MODIFIERS.iter().copied().filter(move |modifier| {
-            for combined in COMBINED_MODIFIERS {
+            for combined in MODIFIER_GROUPS {
                 // if <Ctrl> is enabled, don't emit <LeftCtrl> and <RightCtrl>
                 if combined != modifier && combined.mask() & modifier.mask() == modifier.mask() && combined.mask() & mask == combined.mask() {
                     return false;
@@ -157,11 +279,20 @@ impl ModifierList {
     }
 
     pub fn len(&self) -> u32 {
-        self.modifiers().count() as u32
+        // a combined modifier counts once, a single side counts for each side
+        let builtin: u32 = MODIFIER_GROUPS.iter().map(|combined| {
+            let sides = combined.mask() & self.mask();
+            if sides == combined.mask() {
+                1
+            } else {
+                sides.count_ones()
+            }
+        }).sum();
+        builtin + self.custom_keys().count() as u32
     }
 
     pub fn is_empty(&self) -> bool {
-        self.mask() == 0
+        self.mask() == 0 && self.keys[0].is_none()
     }
 }
 
@@ -170,6 +301,9 @@ impl Display for ModifierList {
         for modifier in self.modifiers() {
             write!(f, "<{}>", modifier)?;
         }
+        for key in self.custom_keys() {
+            write!(f, "<{}>", key)?;
+        }
         Ok(())
     }
 }
@@ -178,22 +312,36 @@ impl FromStr for ModifierList {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let modifiers = s.split('>')
-            .filter(|part| !part.is_empty())
-            .map(|part| {
-                if !part.starts_with('<') {
-                    Err(ParseError::with_message("Invalid modifier"))
-                } else {
-                    Ok(part[1..].parse::<Modifier>()?)
+        let mut list = ModifierList::default();
+        for part in s.split('>').filter(|part| !part.is_empty()) {
+            let name = part
+                .strip_prefix('<')
+                .ok_or_else(|| ParseError::with_message("Invalid modifier"))?;
+            if name == "Hyper" {
+                list.mask |= ModifierList::hyper().mask;
+            } else if let Ok(modifier) = name.parse::<Modifier>() {
+                list.mask |= modifier.mask();
+            } else {
+                let key = name
+                    .parse::<Key>()
+                    .map_err(|_| ParseError::with_message("Invalid modifier"))?;
+                if !list.insert_key(key) {
+                    return Err(ParseError::with_message("Too many custom modifiers"));
                 }
-            })
-            .collect::<Result<Vec<Modifier>, ParseError>>()?;
-        Ok(ModifierList::new(&modifiers))
+            }
+        }
+        Ok(list)
     }
 }
 
 /// A keyboard shortcut consisting of zero or more modifier keys and a non-modifier key
 ///
+/// A shortcut without key, like `<Ctrl><Shift>`, is triggered by pressing its modifiers without any other key.
+/// Pressing another key while it's pressed ends it as [Cancelled](ShortcutState::Cancelled) instead of released,
+/// so a modifier-only shortcut can coexist with the shortcuts that use the same modifiers with a key.
+///
+/// A shortcut prefixed with `@` or `@!` [triggers on release](Trigger) instead of on press.
+///
 /// Examples:
 ///
 /// Create from keys:
@@ -212,28 +360,75 @@ impl FromStr for ModifierList {
 /// # use std::str::FromStr;
 /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
 /// let shortcut: Shortcut = "<Meta>-KeyN".parse()?;
+/// let switch_layout: Shortcut = "<Ctrl><Shift>".parse()?;
+/// assert!(switch_layout.is_modifier_only());
 /// # Ok(())
 /// # }
 /// ```
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub struct Shortcut {
     pub modifiers: ModifierList,
-    pub key: Key,
+    key: Option<Key>,
+    pub trigger: Trigger,
+}
+
+/// The key event that triggers a shortcut
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Default)]
+pub enum Trigger {
+    /// Trigger when the keys of the shortcut are pressed
+    #[default]
+    Press,
+    /// Trigger when the keys of the shortcut are released, written as `@` before the shortcut
+    ///
+    /// Both the [Pressed](ShortcutState::Pressed) and [Released](ShortcutState::Released) events are emitted on release.
+    /// If the shortcut stops matching because another modifier is pressed, it doesn't trigger.
+    Release,
+    /// Trigger on release, unless another key was pressed while the shortcut was held, written as `@!` before the shortcut
+    ReleaseAlone,
+}
+
+impl Trigger {
+    fn prefix(&self) -> &'static str {
+        match self {
+            Trigger::Press => "",
+            Trigger::Release => "@",
+            Trigger::ReleaseAlone => "@!",
+        }
+    }
 }
 
 impl FromStr for Shortcut {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (trigger, s) = if let Some(s) = s.strip_prefix("@!") {
+            (Trigger::ReleaseAlone, s)
+        } else if let Some(s) = s.strip_prefix('@') {
+            (Trigger::Release, s)
+        } else {
+            (Trigger::Press, s)
+        };
         if let Some((modifiers, key)) = s.split_once('-') {
             Ok(Shortcut {
                 modifiers: modifiers.parse()?,
-                key: key.parse()?,
+                key: Some(key.parse()?),
+                trigger,
+            })
+        } else if s.starts_with('<') {
+            let modifiers: ModifierList = s.parse()?;
+            if modifiers.is_empty() {
+                return Err(ParseError::with_message("Empty shortcut"));
+            }
+            Ok(Shortcut {
+                modifiers,
+                key: None,
+                trigger,
             })
         } else {
             Ok(Shortcut {
                 modifiers: ModifierList::default(),
-                key: s.parse()?,
+                key: Some(s.parse()?),
+                trigger,
             })
         }
     }
@@ -241,10 +436,11 @@ impl FromStr for Shortcut {
 
 impl Display for Shortcut {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        if self.modifiers.is_empty() {
-            write!(f, "{}", self.key)
-        } else {
-            write!(f, "{}-{}", self.modifiers, self.key)
+        write!(f, "{}", self.trigger.prefix())?;
+        match self.key {
+            None => write!(f, "{}", self.modifiers),
+            Some(key) if self.modifiers.is_empty() => write!(f, "{}", key),
+            Some(key) => write!(f, "{}-{}", self.modifiers, key),
         }
     }
 }
@@ -252,22 +448,65 @@ impl Display for Shortcut {
 #[cfg(test)]
 mod tests {
     use test_case::test_case;
-    use crate::{Key, Modifier, ModifierList, Shortcut};
+    use crate::{Key, Modifier, ModifierList, Shortcut, Trigger};
 
     #[test_case("KeyP", Shortcut::new(& [], Key::KeyP))]
     #[test_case("<Ctrl>-KeyP", Shortcut::new(& [Modifier::Ctrl], Key::KeyP))]
+    #[test_case("<Ctrl><Shift>", Shortcut::modifiers_only(& [Modifier::Ctrl, Modifier::Shift]))]
+    #[test_case("<RightAlt>", Shortcut::modifiers_only(& [Modifier::AltGr]))]
     #[test_case("<LeftAlt><LeftCtrl>-KeyLeft", Shortcut::new(& [Modifier::LeftCtrl, Modifier::LeftAlt], Key::KeyLeft))]
+    #[test_case("<Shift><CapsLock>-KeyA", Shortcut::new(& [Modifier::CapsLock, Modifier::Shift], Key::KeyA))]
+    #[test_case("<Fn>-KeyF1", Shortcut::new(& [Modifier::Fn], Key::KeyF1))]
+    #[test_case("<LeftCtrl><KeySpace>-KeyJ", Shortcut {
+        modifiers: "<KeySpace><LeftCtrl>".parse().unwrap(),
+        key: Some(Key::KeyJ),
+        trigger: Trigger::Press,
+    })]
+    #[test_case("<KeyTab><KeySpace>-KeyJ", Shortcut {
+        modifiers: "<KeySpace><KeyTab>".parse().unwrap(),
+        key: Some(Key::KeyJ),
+        trigger: Trigger::Press,
+    })]
+    #[test_case("@<Meta>-KeyTab", Shortcut::new(& [Modifier::Meta], Key::KeyTab).with_trigger(Trigger::Release))]
+    #[test_case("@!<Ctrl><Shift>", Shortcut::modifiers_only(& [Modifier::Ctrl, Modifier::Shift]).with_trigger(Trigger::ReleaseAlone))]
+    #[test_case("@KeyF13", Shortcut::new(& [], Key::KeyF13).with_trigger(Trigger::Release))]
     fn shortcut_parse_display_test(s: &str, shortcut: Shortcut) {
         assert_eq!(s, format!("{}", shortcut));
 
         assert_eq!(shortcut, s.parse().unwrap());
     }
 
+    #[test_case("<AltGr>", "<RightAlt>")]
+    #[test_case("<Hyper>", "<Alt><Ctrl><Shift><Meta>")]
+    #[test_case("<KeyLeftShift><KeyCapsLock>", "<LeftShift><CapsLock>"; "modifier keys as custom modifier")]
+    #[test_case("<KeySpace><KeySpace>", "<KeySpace>")]
+    fn modifier_alias(alias: &str, modifiers: &str) {
+        let alias: ModifierList = alias.parse().unwrap();
+        assert_eq!(modifiers, alias.to_string());
+    }
+
+    #[test_case("<Foo>")]
+    #[test_case("Ctrl")]
+    #[test_case("<KeyA><KeyB><KeyC><KeyD><KeyE>"; "too many custom modifiers")]
+    fn modifier_parse_error(s: &str) {
+        assert!(s.parse::<ModifierList>().is_err());
+    }
+
     #[test_case(& [Modifier::Ctrl])]
     #[test_case(& [Modifier::LeftAlt, Modifier::LeftCtrl])]
     #[test_case(& [Modifier::Shift, Modifier::Meta])]
+    #[test_case(& [Modifier::RightShift, Modifier::LeftMeta])]
     fn test_modifier_list(modifiers: &[Modifier]) {
-        assert_eq!(modifiers.to_vec(), ModifierList::new(modifiers).modifiers().collect::<Vec<_>>())
+        let list = ModifierList::new(modifiers);
+        assert_eq!(modifiers.to_vec(), list.modifiers().collect::<Vec<_>>());
+        assert_eq!(list.modifiers().count() as u32, list.len());
+    }
+
+    #[test]
+    fn test_modifier_key() {
+        for modifier in crate::ALL_MODIFIERS {
+            assert_eq!(modifier.mask() & Modifier::mask_from_key(modifier.key()), Modifier::mask_from_key(modifier.key()));
+        }
     }
 }
 
@@ -275,12 +514,39 @@ impl Shortcut {
     pub fn new(modifiers: &[Modifier], key: Key) -> Self {
         Shortcut {
             modifiers: ModifierList::new(modifiers),
-            key,
+            key: Some(key),
+            trigger: Trigger::Press,
         }
     }
 
+    /// Create a shortcut that is triggered by pressing only the modifiers
+    pub fn modifiers_only(modifiers: &[Modifier]) -> Self {
+        Shortcut {
+            modifiers: ModifierList::new(modifiers),
+            key: None,
+            trigger: Trigger::Press,
+        }
+    }
+
+    /// The key of the shortcut, `None` for shortcuts that only consist of modifiers
+    pub fn key(&self) -> Option<Key> {
+        self.key
+    }
+
+    /// Check if the shortcut only consists of modifiers
+    pub fn is_modifier_only(&self) -> bool {
+        self.key.is_none()
+    }
+
+    /// Change the key event that triggers the shortcut
+    pub fn with_trigger(self, trigger: Trigger) -> Self {
+        Shortcut { trigger, ..self }
+    }
+
     pub fn identifier(&self) -> String {
         self.to_string()
+            .replace("@!", "release_alone_")
+            .replace('@', "release_")
             .replace(['<', '>'], "")
             .replace('-', "_")
     }
@@ -288,22 +554,90 @@ impl Shortcut {
 
 impl Shortcut {
     pub fn is_triggered(&self, active_keys: &HashSet<Key>) -> bool {
-        let desired_mask = self.modifiers.mask();
         let pressed_mask = active_keys
             .iter()
             .fold(0, |mask, key| mask | Modifier::mask_from_key(*key));
 
-        let desired_presses = desired_mask & pressed_mask;
-        let modifiers_match = (desired_presses == pressed_mask)
-            && (desired_presses.count_ones() == self.modifiers.len());
+        let active_keys: KeySet = active_keys.iter().copied().collect();
+        self.is_triggered_by(&active_keys, pressed_mask)
+    }
+
+    /// Check if the shortcut is triggered by a set of held keys
+    ///
+    /// `pressed_mask` is the combined [mask](Modifier::mask_from_key) of all held modifier keys.
+    pub fn is_triggered_by(&self, active_keys: &KeySet, pressed_mask: u16) -> bool {
+        self.is_triggered_with(active_keys, pressed_mask, &MatchPolicy::default())
+    }
+
+    /// Check if the shortcut is triggered by a set of held keys, using a matching policy
+    ///
+    /// Only the modifier rules of the policy are applied,
+    /// the [ordering](MatchPolicy::strict_order) and [re-arm](MatchPolicy::rearm) rules depend on the order of the key events
+    /// and are applied by the [ShortcutMatcher].
+    pub fn is_triggered_with(&self, active_keys: &KeySet, pressed_mask: u16, policy: &MatchPolicy) -> bool {
+        let ignored = policy.ignored_modifiers.mask();
+        let required = self.modifiers.mask() & !ignored;
+        // holding the key of the shortcut doesn't count as holding an extra modifier
+        let own = self.key.map_or(0, Modifier::mask_from_key) & !required;
+        let pressed_mask = pressed_mask & !ignored & !own;
+        let modifiers_match = MODIFIER_GROUPS.iter().all(|combined| {
+            let required = required & combined.mask();
+            let pressed = pressed_mask & combined.mask();
+            match (required, policy.allow_extra_modifiers) {
+                (0, false) => pressed == 0,
+                (0, true) => true,
+                // a combined modifier is pressed with either side, but not both
+                (required, false) if required == combined.mask() => pressed.count_ones() == 1,
+                (required, false) => pressed == required,
+                (required, true) => pressed & required != 0,
+            }
+        });
+        let custom_held = self
+            .modifiers
+            .custom_keys()
+            .filter(|key| !policy.ignored_modifiers.has_custom_key(*key))
+            .all(|key| active_keys.contains(key));
+        let key_held = match self.key {
+            Some(key) => active_keys.contains(key),
+            // no key besides the modifiers can be held
+            None => active_keys
+                .iter()
+                .all(|key| Modifier::mask_from_key(key) != 0 || self.modifiers.has_custom_key(key)),
+        };
+        modifiers_match && custom_held && key_held
+    }
+
+    /// Check if the shortcut can be triggered by the same keys as another shortcut
+    ///
+    /// Custom modifiers don't stop other shortcuts from triggering,
+    /// so `<KeySpace>-KeyJ` overlaps with `KeyJ`.
+    pub fn overlaps(&self, other: &Shortcut) -> bool {
+        self.key == other.key && MODIFIER_GROUPS.iter().all(|combined| {
+            let sides = self.modifiers.mask() & combined.mask();
+            let other_sides = other.modifiers.mask() & combined.mask();
+            (sides == 0) == (other_sides == 0) && (sides == 0 || sides & other_sides != 0)
+        })
+    }
 
-        modifiers_match && active_keys.contains(&self.key)
+    /// Check if the shortcut is a more specific variant of another shortcut with the same key
+    ///
+    /// A shortcut is more specific if it allows fewer sides of the modifiers of the other shortcut,
+    /// or requires additional modifiers. Both `<LeftCtrl>-KeyP` and `<Ctrl><Shift>-KeyP` are more specific than `<Ctrl>-KeyP`.
+    pub fn is_more_specific_than(&self, other: &Shortcut) -> bool {
+        self.key == other.key
+            && self.modifiers != other.modifiers
+            && other.modifiers.custom_keys().all(|key| self.modifiers.has_custom_key(key))
+            && MODIFIER_GROUPS.iter().all(|combined| {
+                let sides = self.modifiers.mask() & combined.mask();
+                let other_sides = other.modifiers.mask() & combined.mask();
+                other_sides == 0 || (sides != 0 && sides & !other_sides == 0)
+            })
     }
 }
 
 #[cfg(test)]
 mod triggered_tests {
-    use crate::{Key, Shortcut};
+    use crate::{Key, KeySet, MatchPolicy, Modifier, ModifierList, Shortcut};
     use test_case::test_case;
 
     #[test_case("<Ctrl>-KeyP", & [] => false)]
@@ -320,17 +654,175 @@ mod triggered_tests {
     #[test_case("<LeftCtrl><LeftAlt>-KeyLeft", & [Key::KeyLeftCtrl, Key::KeyLeftAlt, Key::KeyLeft] => true)]
     #[test_case("<LeftCtrl><LeftAlt>-KeyLeft", & [Key::KeyLeftCtrl, Key::KeyRightAlt, Key::KeyLeft] => false)]
     #[test_case("<Ctrl><Alt>-KeyLeft", & [Key::KeyLeftCtrl, Key::KeyRightAlt, Key::KeyLeft] => true)]
+    #[test_case("<CapsLock>-KeyH", & [Key::KeyCapsLock, Key::KeyH] => true)]
+    #[test_case("KeyH", & [Key::KeyCapsLock, Key::KeyH] => false)]
+    #[test_case("<Fn>-KeyF1", & [Key::KeyFn, Key::KeyF1] => true)]
+    #[test_case("<AltGr>-KeyE", & [Key::KeyRightAlt, Key::KeyE] => true)]
+    #[test_case("<Hyper>-KeyH", & [Key::KeyLeftCtrl, Key::KeyLeftAlt, Key::KeyLeftShift, Key::KeyLeftMeta, Key::KeyH] => true)]
+    #[test_case("<Hyper>-KeyH", & [Key::KeyLeftCtrl, Key::KeyLeftAlt, Key::KeyLeftShift, Key::KeyH] => false)]
+    #[test_case("<KeySpace>-KeyJ", & [Key::KeySpace, Key::KeyJ] => true)]
+    #[test_case("<KeySpace>-KeyJ", & [Key::KeyJ] => false)]
+    #[test_case("<KeySpace>-KeyJ", & [Key::KeyLeftCtrl, Key::KeySpace, Key::KeyJ] => false)]
+    #[test_case("KeyJ", & [Key::KeySpace, Key::KeyJ] => true; "custom modifier isn't an extra modifier")]
+    #[test_case("KeyCapsLock", & [Key::KeyCapsLock] => true; "own modifier key")]
+    #[test_case("<LeftCtrl>-KeyLeftCtrl", & [Key::KeyLeftCtrl] => true)]
+    #[test_case("KeyLeftCtrl", & [Key::KeyLeftCtrl, Key::KeyRightCtrl] => false)]
+    #[test_case("<Ctrl><Shift>", & [Key::KeyLeftCtrl, Key::KeyRightShift] => true; "modifier only")]
+    #[test_case("<Ctrl><Shift>", & [Key::KeyLeftCtrl] => false; "modifier only, missing modifier")]
+    #[test_case("<Ctrl><Shift>", & [Key::KeyLeftCtrl, Key::KeyRightShift, Key::KeyX] => false; "modifier only, other key")]
+    #[test_case("<KeySpace>", & [Key::KeySpace] => true; "modifier only, custom modifier")]
     fn shortcut_triggered(s: &str, keys: &[Key]) -> bool {
         let shortcut: Shortcut = s.parse().unwrap();
-        shortcut.is_triggered(&keys.into_iter().copied().collect())
+        let triggered = shortcut.is_triggered(&keys.iter().copied().collect());
+        assert_eq!(triggered, triggered_with(s, keys, MatchPolicy::default()));
+        triggered
+    }
+
+    fn triggered_with(s: &str, keys: &[Key], policy: MatchPolicy) -> bool {
+        let shortcut: Shortcut = s.parse().unwrap();
+        let active_keys: KeySet = keys.iter().copied().collect();
+        let pressed_mask = keys.iter().fold(0, |mask, key| mask | Modifier::mask_from_key(*key));
+        shortcut.is_triggered_with(&active_keys, pressed_mask, &policy)
+    }
+
+    #[test_case("<Ctrl>-KeyP", & [Key::KeyLeftCtrl, Key::KeyLeftAlt, Key::KeyP] => true)]
+    #[test_case("<Ctrl>-KeyP", & [Key::KeyLeftCtrl, Key::KeyRightCtrl, Key::KeyP] => true)]
+    #[test_case("<LeftCtrl>-KeyP", & [Key::KeyRightCtrl, Key::KeyLeftShift, Key::KeyP] => false)]
+    #[test_case("<Ctrl><Alt>-KeyP", & [Key::KeyLeftCtrl, Key::KeyP] => false)]
+    #[test_case("KeyP", & [Key::KeyLeftMeta, Key::KeyP] => true)]
+    fn superset_policy(s: &str, keys: &[Key]) -> bool {
+        triggered_with(s, keys, MatchPolicy {
+            allow_extra_modifiers: true,
+            ..MatchPolicy::default()
+        })
+    }
+
+    #[test_case("<Ctrl>-KeyP", & [Key::KeyLeftCtrl, Key::KeyLeftShift, Key::KeyP] => true)]
+    #[test_case("<Ctrl>-KeyP", & [Key::KeyLeftCtrl, Key::KeyLeftAlt, Key::KeyP] => false)]
+    #[test_case("<Ctrl><Shift>-KeyP", & [Key::KeyLeftCtrl, Key::KeyP] => true)]
+    fn ignored_modifiers_policy(s: &str, keys: &[Key]) -> bool {
+        triggered_with(s, keys, MatchPolicy {
+            ignored_modifiers: ModifierList::new(&[Modifier::Shift]),
+            ..MatchPolicy::default()
+        })
+    }
+
+    #[test]
+    fn combined_modifier_needs_other_modifiers() {
+        assert!(!triggered_with("<Ctrl><LeftAlt>-KeyP", &[Key::KeyLeftCtrl, Key::KeyRightCtrl, Key::KeyP], MatchPolicy::default()));
+    }
+
+    #[test_case("<Ctrl>-KeyP", "<Ctrl>-KeyP" => true)]
+    #[test_case("<Ctrl>-KeyP", "<LeftCtrl>-KeyP" => true)]
+    #[test_case("<LeftCtrl>-KeyP", "<RightCtrl>-KeyP" => false)]
+    #[test_case("<Ctrl>-KeyP", "<Ctrl><Shift>-KeyP" => false)]
+    #[test_case("<Ctrl>-KeyP", "<Ctrl>-KeyO" => false)]
+    #[test_case("KeyP", "KeyP" => true)]
+    fn shortcut_overlaps(a: &str, b: &str) -> bool {
+        let a: Shortcut = a.parse().unwrap();
+        let b: Shortcut = b.parse().unwrap();
+        assert_eq!(a.overlaps(&b), b.overlaps(&a));
+        a.overlaps(&b)
+    }
+
+    #[test_case("<LeftCtrl>-KeyP", "<Ctrl>-KeyP" => true)]
+    #[test_case("<Ctrl>-KeyP", "<LeftCtrl>-KeyP" => false)]
+    #[test_case("<Ctrl><Shift>-KeyP", "<Ctrl>-KeyP" => true)]
+    #[test_case("<LeftCtrl>-KeyP", "<Ctrl><Shift>-KeyP" => false)]
+    #[test_case("<Ctrl>-KeyP", "<Ctrl>-KeyP" => false)]
+    #[test_case("<LeftCtrl>-KeyP", "<Ctrl>-KeyO" => false)]
+    fn shortcut_more_specific(a: &str, b: &str) -> bool {
+        let a: Shortcut = a.parse().unwrap();
+        let b: Shortcut = b.parse().unwrap();
+        a.is_more_specific_than(&b)
+    }
+}
+
+/// How repeat events are generated for a held shortcut
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    /// Emit a repeat for every autorepeat event the device sends for the key
+    Kernel,
+    /// Emit repeats at a fixed rate, for devices that don't repeat keys themselves
+    Rate {
+        /// Time between the press and the first repeat
+        delay: Duration,
+        /// Time between consecutive repeats
+        interval: Duration,
+    },
+}
+
+/// Rules for matching held keys against a shortcut
+///
+/// The default policy requires exactly the modifiers of the shortcut to be held, in any order,
+/// and triggers the shortcut again after an extra modifier is released.
+///
+/// A held `CapsLock` or `Fn` key counts as an extra modifier, so it blocks shortcuts that don't include it.
+/// Add them to [`ignored_modifiers`](MatchPolicy::ignored_modifiers) if they should be ignored instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MatchPolicy {
+    /// Only trigger the shortcut when the modifiers are pressed before the key
+    pub strict_order: bool,
+    /// Also trigger the shortcut when modifiers that aren't part of it are held
+    pub allow_extra_modifiers: bool,
+    /// Modifiers that are ignored, both when held and when part of the shortcut
+    pub ignored_modifiers: ModifierList,
+    /// Trigger the shortcut again when an extra modifier that released it is released while the key is still held
+    pub rearm: bool,
+}
+
+impl Default for MatchPolicy {
+    fn default() -> Self {
+        MatchPolicy {
+            strict_order: false,
+            allow_extra_modifiers: false,
+            ignored_modifiers: ModifierList::default(),
+            rearm: true,
+        }
     }
 }
 
+/// Per shortcut settings
+///
+/// Example:
+///
+/// ```rust
+/// # use evdev_shortcut::ShortcutOptions;
+/// # use std::time::Duration;
+/// let quit = ShortcutOptions {
+///     hold: Some(Duration::from_millis(800)),
+///     ..ShortcutOptions::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShortcutOptions {
+    /// Emit a [Held](ShortcutState::Held) event once the shortcut has been held for this long
+    ///
+    /// Releasing the shortcut before then emits [Cancelled](ShortcutState::Cancelled) instead of [Released](ShortcutState::Released).
+    pub hold: Option<Duration>,
+    /// Emit [Repeat](ShortcutState::Repeat) events while the shortcut is held
+    pub repeat: Option<Repeat>,
+    /// Only trigger the shortcut by keys from the selected devices
+    ///
+    /// Scoped shortcuts are matched against the keys held on the device that sent the key event,
+    /// instead of the keys held on all devices combined.
+    pub device: Option<DeviceSelector>,
+    /// Matching policy for this shortcut, overriding the policy of the matcher
+    pub policy: Option<MatchPolicy>,
+}
+
 /// Whether the shortcut was pressed or released
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum ShortcutState {
     Pressed,
     Released,
+    /// The shortcut has been held for its [hold threshold](ShortcutOptions::hold)
+    Held,
+    /// The shortcut was released before its [hold threshold](ShortcutOptions::hold) was reached,
+    /// or another key was pressed while a modifier-only shortcut was pressed
+    Cancelled,
+    /// The shortcut is still held, emitted when [repeat](ShortcutOptions::repeat) is enabled
+    Repeat,
 }
 
 impl ShortcutState {
@@ -338,6 +830,9 @@ impl ShortcutState {
         match self {
             ShortcutState::Pressed => "pressed",
             ShortcutState::Released => "released",
+            ShortcutState::Held => "held",
+            ShortcutState::Cancelled => "cancelled",
+            ShortcutState::Repeat => "repeat",
         }
     }
 }
@@ -348,9 +843,69 @@ impl Display for ShortcutState {
     }
 }
 
+/// Why a shortcut was released
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ReleaseReason {
+    /// The held keys changed so they no longer trigger the shortcut
+    KeyReleased,
+    /// The device holding the keys of the shortcut was disconnected or failed
+    DeviceLost,
+    /// The shortcut was removed while it was held
+    ShortcutRemoved,
+    /// Listening for shortcuts stopped while the shortcut was held
+    ListenerStopped,
+}
+
+impl ReleaseReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReleaseReason::KeyReleased => "key released",
+            ReleaseReason::DeviceLost => "device lost",
+            ReleaseReason::ShortcutRemoved => "shortcut removed",
+            ReleaseReason::ListenerStopped => "listener stopped",
+        }
+    }
+}
+
+impl Display for ReleaseReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 /// Event emitted when a shortcut is pressed or released.
+///
+/// New fields can be added to the event, use [ShortcutEvent::new] to create one.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct ShortcutEvent {
     pub shortcut: Shortcut,
     pub state: ShortcutState,
+    /// Time of the key event that caused the state change, as reported by the kernel
+    ///
+    /// For [Held](ShortcutState::Held) and generated [Repeat](ShortcutState::Repeat) events this is the time they were due.
+    pub time: SystemTime,
+    /// Device of the key event that caused the state change
+    ///
+    /// For timed events this is the device that pressed the shortcut.
+    /// `None` if the key events weren't read from a device.
+    pub device: Option<Arc<DeviceInfo>>,
+    /// How long the shortcut has been held, `None` for [Pressed](ShortcutState::Pressed) events
+    pub held_for: Option<Duration>,
+    /// Why the shortcut was released, only set for [Released](ShortcutState::Released) and [Cancelled](ShortcutState::Cancelled) events
+    pub reason: Option<ReleaseReason>,
+}
+
+impl ShortcutEvent {
+    /// Create an event that happened now, without device, hold duration or release reason
+    pub fn new(shortcut: Shortcut, state: ShortcutState) -> Self {
+        ShortcutEvent {
+            shortcut,
+            state,
+            time: SystemTime::now(),
+            device: None,
+            held_for: None,
+            reason: None,
+        }
+    }
 }
\ No newline at end of file
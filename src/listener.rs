@@ -1,29 +1,117 @@
-use evdev::Device;
-use std::collections::HashSet;
-use std::convert::TryFrom;
-use std::sync::{Arc, Mutex};
-use crate::{Shortcut, DeviceOpenError, Key, ShortcutEvent, ShortcutState};
-use std::path::Path;
+use arc_swap::{ArcSwap, Guard};
+use evdev::{Device, InputEvent};
+use inotify::{EventMask, Inotify, WatchMask};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use crate::{Chord, ChordConflict, ChordEvent, Key, KeySequence, KeySet, MatchEvent, MatchPolicy, Modifier, SequenceConflict, SequenceEvent, SequenceState, Shortcut, ShortcutConflict, ShortcutMatcher, ShortcutOptions, ShortcutSet, DeviceOpenError, DeviceReadError, ShortcutEvent, ShortcutState, Tap, TapEvent};
+use crate::grab::{can_grab, grabbed_device_events, Grab};
+use crate::decode::DecodedEvent;
+use crate::discovery::{device_info, is_own_device};
+use crate::{DeviceFilter, DeviceInfo};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::sync::Notify;
 use async_stream::stream;
 use futures::pin_mut;
 use futures::{Stream, StreamExt};
-use futures::stream::{iter};
-use tracing::{debug, trace, info};
+use futures::future;
+use futures::stream::{iter, select_all, BoxStream, SelectAll};
+use tracing::{debug, trace, info, warn};
+
+/// Directory that is watched for new devices when hotplug is enabled
+const INPUT_DIR: &str = "/dev/input";
+
+/// Error returned when reading from a device that has been removed
+const ENODEV: i32 = 19;
+
+/// Device used to create the virtual keyboard for grabbed devices
+const UINPUT_DEVICE: &str = "/dev/uinput";
+
+/// Event emitted by a [ShortcutListener]
+#[derive(Debug, Clone)]
+pub enum ListenerEvent {
+    /// A shortcut was pressed or released
+    Shortcut(ShortcutEvent),
+    /// A key sequence progressed, completed or was aborted
+    Sequence(SequenceEvent),
+    /// A key or modifier was tapped
+    Tap(TapEvent),
+    /// A chord was pressed or released
+    Chord(ChordEvent),
+    /// An input device failed and is no longer being listened to
+    ///
+    /// The remaining devices will keep being listened to.
+    /// Shortcuts held by the keys of the device are released before this event,
+    /// with [DeviceLost](crate::ReleaseReason::DeviceLost) as reason, the same goes for [DeviceRemoved](ListenerEvent::DeviceRemoved).
+    DeviceError(DeviceReadError),
+    /// A new device matching the [hotplug](ShortcutListener::set_hotplug) filters was plugged in and is now being listened to
+    ///
+    /// Only emitted when [hotplug](ShortcutListener::set_hotplug) is enabled.
+    DeviceAdded(PathBuf),
+    /// A device was unplugged and is no longer being listened to
+    ///
+    /// Only emitted when [hotplug](ShortcutListener::set_hotplug) is enabled,
+    /// otherwise removed devices are reported as [DeviceError](ListenerEvent::DeviceError).
+    DeviceRemoved(PathBuf),
+    /// The emergency release combo was pressed and grabbed devices have been released
+    ///
+    /// Shortcuts keep being listened to, but their keys are no longer swallowed.
+    GrabReleased,
+}
+
+impl From<MatchEvent> for ListenerEvent {
+    fn from(event: MatchEvent) -> Self {
+        match event {
+            MatchEvent::Shortcut(event) => ListenerEvent::Shortcut(event),
+            MatchEvent::Sequence(event) => ListenerEvent::Sequence(event),
+            MatchEvent::Tap(event) => ListenerEvent::Tap(event),
+            MatchEvent::Chord(event) => ListenerEvent::Chord(event),
+        }
+    }
+}
+
+/// Event from one of the sources the listener reads from
+#[derive(Debug)]
+pub(crate) enum SourceEvent {
+    /// Raw input event tagged with the device that emitted it
+    ///
+    /// `grabbed` is whether the device was grabbed when the event was read.
+    Input {
+        device: Arc<DeviceInfo>,
+        event: io::Result<InputEvent>,
+        grabbed: bool,
+    },
+    /// Keys held on a device, read when the device is opened
+    KeyState {
+        device: Arc<DeviceInfo>,
+        keys: KeySet,
+    },
+    /// A new device node appeared
+    DeviceAppeared(PathBuf),
+    /// The attributes of a device node changed, such as its permissions
+    DeviceChanged(PathBuf),
+    /// Watching for new devices failed
+    WatchError(io::Error),
+}
+
+type Source = BoxStream<'static, SourceEvent>;
+
+/// Open a newly appeared device, returns `None` if the device doesn't match the hotplug filters
+type DeviceOpener = Box<dyn Fn(&Path) -> io::Result<Option<Source>> + Send>;
 
 /// A listener for shortcut events
 ///
 /// Example:
 ///
 /// ```rust,no_run
-/// # use std::path::PathBuf;
-/// # use glob::GlobError;
-/// # use evdev_shortcut::{ShortcutListener, Shortcut, Modifier, Key};
+/// # use evdev_shortcut::{discover, DeviceFilter, ShortcutListener, Shortcut, Modifier, Key};
 /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
 /// let listener = ShortcutListener::new();
 /// listener.add(Shortcut::new(&[Modifier::Meta], Key::KeyN));
 ///
-/// let devices =
-///     glob::glob("/dev/input/by-id/*-kbd")?.collect::<Result<Vec<PathBuf>, GlobError>>()?;
+/// let devices = discover(&[DeviceFilter::LetterKeys])?;
 ///
 /// let stream = listener.listen(&devices)?;
 /// # Ok(())
@@ -31,7 +119,29 @@ use tracing::{debug, trace, info};
 /// ```
 #[derive(Default)]
 pub struct ShortcutListener {
-    shortcuts: Arc<Mutex<HashSet<Shortcut>>>,
+    shortcuts: Arc<ArcSwap<ShortcutSet>>,
+    /// Filters for devices that are plugged in while listening, hotplug is disabled if empty
+    hotplug: Vec<DeviceFilter>,
+    grab: bool,
+    grab_release: Option<Shortcut>,
+    sequence_timeout: Option<Duration>,
+    tap_timeout: Option<Duration>,
+    multi_tap_window: Option<Duration>,
+    chord_window: Option<Duration>,
+    policy: MatchPolicy,
+    most_specific_wins: bool,
+    signals: Arc<StreamSignals>,
+}
+
+/// Signals from a listener to its streams
+#[derive(Default)]
+struct StreamSignals {
+    /// Shortcuts were removed, held shortcuts that were removed need to be released
+    removed: Notify,
+    /// Number of times the listener was stopped, streams end once it changed since they were created
+    stops: AtomicU64,
+    /// Wakes the streams when the listener is stopped
+    stop: Notify,
 }
 
 impl ShortcutListener {
@@ -42,74 +152,1179 @@ impl ShortcutListener {
     /// Listen for shortcuts on the provided set of input devices.
     ///
     /// Note that you need to register shortcuts using [add](ShortcutListener::add) to get any events.
+    ///
+    /// Devices that fail while listening are silently dropped,
+    /// use [listen_events](ShortcutListener::listen_events) to get notified about device errors.
     pub fn listen<P: AsRef<Path>>(&self, devices: &[P]) -> Result<impl Stream<Item=ShortcutEvent>, DeviceOpenError> {
-        let shortcuts = self.shortcuts.clone();
+        Ok(self.listen_events(devices)?.filter_map(|event| async move {
+            match event {
+                ListenerEvent::Shortcut(event) => Some(event),
+                _ => None,
+            }
+        }))
+    }
 
+    /// Listen for shortcuts on the provided set of input devices, including device status events.
+    ///
+    /// When a device fails, a [DeviceError](ListenerEvent::DeviceError) is emitted for it
+    /// and the other devices keep being listened to.
+    ///
+    /// Keys that are already held when a device is opened are pressed as soon as the stream starts.
+    /// If the kernel drops events because they weren't read in time, evdev compares the held keys with the device state
+    /// and emits key events for the keys that changed in the meantime, so shortcuts are pressed or released accordingly.
+    pub fn listen_events<P: AsRef<Path>>(&self, devices: &[P]) -> Result<impl Stream<Item=ListenerEvent>, DeviceOpenError> {
+        let grab = if self.grab {
+            let release = self.grab_release.clone().unwrap_or_else(default_grab_release);
+            let grab = Grab::new(release).map_err(|error| {
+                warn!(%error, "failed to create virtual keyboard");
+                DeviceOpenError { device: UINPUT_DEVICE.into() }
+            })?;
+            Some(grab)
+        } else {
+            None
+        };
+        let released = grab.as_ref().map(|grab| grab.released().clone());
+
+        let mut open_devices = HashMap::new();
         let devices = devices
             .iter()
             .map(|path| {
                 let path = path.as_ref();
-                let res = Device::open(path).map_err(|_| DeviceOpenError { device: path.into() });
+                let res = Device::open(path)
+                    .and_then(|device| open_device(path, device, released.as_ref()))
+                    .map_err(|_| DeviceOpenError { device: path.into() });
                 debug!(device = ?path, success = res.is_ok(), "opening input device");
+                let canonical = path.canonicalize().unwrap_or_else(|_| path.into());
+                open_devices.insert(canonical, path.into());
                 res
             })
-            .collect::<Result<Vec<Device>, DeviceOpenError>>()?;
-        let events = iter(devices.into_iter().flat_map(|device| device.into_event_stream()))
-            .flatten();
-
-        Ok(stream! {
-            let mut active_keys = HashSet::new();
-            let mut pressed_shortcuts = HashSet::new();
-
-            pin_mut!(events);
-
-            while let Some(Ok(event)) = events.next().await {
-                trace!(?event, "evdev event");
-                if let Ok(key) = Key::try_from(event.code()) {
-                    match event.value() {
-                        1 => active_keys.insert(key),
-                        0 => active_keys.remove(&key),
-                        _ => false,
-                    };
-                }
+            .collect::<Result<Vec<_>, DeviceOpenError>>()?;
 
-                let shortcuts: Vec<_> = shortcuts.lock().unwrap().iter().cloned().collect();
-
-                for shortcut in shortcuts {
-                    let is_triggered = shortcut.is_triggered(&active_keys);
-                    let was_triggered = pressed_shortcuts.contains(&shortcut);
-                    if is_triggered && !was_triggered {
-                        pressed_shortcuts.insert(shortcut.clone());
-                        info!(?shortcut, "pressed");
-                        yield ShortcutEvent {
-                            shortcut,
-                            state: ShortcutState::Pressed,
-                        };
-                    } else if !is_triggered && was_triggered {
-                        pressed_shortcuts.remove(&shortcut);
-                        info!(?shortcut, "released");
-                        yield ShortcutEvent {
-                            shortcut,
-                            state: ShortcutState::Released,
-                        };
-                    }
-                }
-            }
-        })
+        // poll all devices concurrently, so every device gets a fair chance to produce events
+        let mut sources = select_all(devices);
+        let opener: Option<DeviceOpener> = if !self.hotplug.is_empty() {
+            sources.push(watch_devices(Path::new(INPUT_DIR)).boxed());
+            let filters = self.hotplug.clone();
+            Some(Box::new(move |path: &Path| open_new_device(path, &filters, released.as_ref())))
+        } else {
+            None
+        };
+        let mut matcher = ShortcutMatcher::new();
+        matcher.set_policy(self.policy);
+        matcher.set_most_specific_wins(self.most_specific_wins);
+        if let Some(timeout) = self.sequence_timeout {
+            matcher.set_sequence_timeout(timeout);
+        }
+        if let Some(timeout) = self.tap_timeout {
+            matcher.set_tap_timeout(timeout);
+        }
+        if let Some(window) = self.multi_tap_window {
+            matcher.set_multi_tap_window(window);
+        }
+        if let Some(window) = self.chord_window {
+            matcher.set_chord_window(window);
+        }
+        Ok(shortcut_stream(self.shortcuts.clone(), matcher, sources, open_devices, opener, grab, self.signals.clone()))
     }
 
-    /// Returns `true` if the shortcut was not previously listened to
+    /// Enable or disable exclusive access to the input devices
+    ///
+    /// When enabled, the devices are grabbed so no other application receives their events directly.
+    /// Keys that trigger a shortcut are swallowed, all other keys are forwarded to the focused application
+    /// through a virtual keyboard.
+    /// Shortcuts that [trigger on release](crate::Trigger) are only known to trigger once their keys are released,
+    /// so their keys are forwarded.
+    ///
+    /// Devices that are opened while keys are held are only grabbed once those keys are released.
+    /// Devices that report events other than keys, like mice or keyboards with a touchpad, are never grabbed.
+    ///
+    /// To prevent a bug from locking the user out of their keyboard, pressing the
+    /// [release combo](ShortcutListener::set_grab_release) releases all grabbed devices.
+    ///
+    /// Only affects streams created after the setting is changed.
+    pub fn set_grab(&mut self, enabled: bool) {
+        self.grab = enabled;
+    }
+
+    /// Set the shortcut that releases grabbed devices, defaults to `<LeftAlt><LeftCtrl><LeftShift>-KeyEsc`
+    pub fn set_grab_release(&mut self, shortcut: Shortcut) {
+        self.grab_release = Some(shortcut);
+    }
+
+    /// Listen to devices that are plugged in while listening and match any of the filters
+    ///
+    /// When enabled, `/dev/input` is watched for new devices and any new device matching the filters is listened to
+    /// in addition to the devices passed to [listen](ShortcutListener::listen).
+    /// Passing no filters disables hotplug, which is the default.
+    /// Added and removed devices are reported by [listen_events](ShortcutListener::listen_events).
+    ///
+    /// Only affects streams created after the setting is changed.
+    pub fn set_hotplug(&mut self, filters: &[DeviceFilter]) {
+        self.hotplug = filters.to_vec();
+    }
+
+    /// Set the matching policy for shortcuts that don't have their own [policy](ShortcutOptions::policy)
+    ///
+    /// Only affects streams created after the setting is changed.
+    pub fn set_match_policy(&mut self, policy: MatchPolicy) {
+        self.policy = policy;
+    }
+
+    /// When enabled, only the most specific of the shortcuts that trigger on the same key event triggers
+    ///
+    /// See [ShortcutMatcher::set_most_specific_wins].
+    ///
+    /// Only affects streams created after the setting is changed.
+    pub fn set_most_specific_wins(&mut self, enabled: bool) {
+        self.most_specific_wins = enabled;
+    }
+
+    /// Set the time allowed between the strokes of a key sequence, defaults to one second
+    ///
+    /// Only affects streams created after the setting is changed.
+    pub fn set_sequence_timeout(&mut self, timeout: Duration) {
+        self.sequence_timeout = Some(timeout);
+    }
+
+    /// Set the maximum time a key can be held for to still count as a tap, defaults to 200ms
+    ///
+    /// Only affects streams created after the setting is changed.
+    pub fn set_tap_timeout(&mut self, timeout: Duration) {
+        self.tap_timeout = Some(timeout);
+    }
+
+    /// Set the time allowed between the release of a tap and the press of the next tap of a multi-tap, defaults to 250ms
+    ///
+    /// Only affects streams created after the setting is changed.
+    pub fn set_multi_tap_window(&mut self, window: Duration) {
+        self.multi_tap_window = Some(window);
+    }
+
+    /// Set the time allowed between the first and the last key press of a chord, defaults to 50ms
+    ///
+    /// Only affects streams created after the setting is changed.
+    pub fn set_chord_window(&mut self, window: Duration) {
+        self.chord_window = Some(window);
+    }
+
+    /// Returns `true` if the shortcut was added, `false` if it was already listened to or was rejected
+    ///
+    /// A shortcut that overlaps with the first stroke of a key sequence is rejected with a warning,
+    /// like [add_sequence](ShortcutListener::add_sequence) rejects sequences that start with a shortcut.
+    /// Use [add_checked](ShortcutListener::add_checked) to tell a rejected shortcut apart from one that was already listened to.
     pub fn add(&self, shortcut: Shortcut) -> bool {
-        self.shortcuts.lock().unwrap().insert(shortcut)
+        if self.reject_overlapping(&shortcut) {
+            return false;
+        }
+        self.update_set(|shortcuts| shortcuts.insert(shortcut.clone()))
+    }
+
+    /// Returns `true` if the shortcut was not previously listened to
+    ///
+    /// Unlike [add](ShortcutListener::add), a shortcut that [conflicts](crate::find_conflicts) with a shortcut
+    /// that is already listened to, or can never trigger, is rejected.
+    /// Overlaps are allowed if [most specific wins](ShortcutListener::set_most_specific_wins) is enabled
+    /// and one of the shortcuts is more specific than the other.
+    /// A shortcut that overlaps with the first stroke of a key sequence is rejected as well.
+    pub fn add_checked(&self, shortcut: Shortcut) -> Result<bool, ShortcutConflict> {
+        self.update_set(|shortcuts| shortcuts.insert_checked(shortcut.clone(), self.most_specific_wins))
+    }
+
+    /// Returns `true` if the shortcut was added, `false` if it was already listened to or was rejected
+    ///
+    /// If the shortcut was already listened to, its options are replaced.
+    /// A shortcut that overlaps with the first stroke of a key sequence is rejected with a warning.
+    pub fn add_with(&self, shortcut: Shortcut, options: ShortcutOptions) -> bool {
+        if self.reject_overlapping(&shortcut) {
+            return false;
+        }
+        self.update_set(|shortcuts| shortcuts.insert_with(shortcut.clone(), options.clone()))
+    }
+
+    /// Returns `true` if the sequence was not previously listened to
+    ///
+    /// Sequences are reported by [listen_events](ShortcutListener::listen_events) as [Sequence](ListenerEvent::Sequence) events.
+    /// A sequence that can't be told apart from a registered shortcut or sequence while typing is rejected.
+    pub fn add_sequence(&self, sequence: KeySequence) -> Result<bool, SequenceConflict> {
+        self.update_set(|shortcuts| shortcuts.insert_sequence(sequence.clone()))
+    }
+
+    /// Returns `true` if the sequence was previously listened to
+    ///
+    /// If the sequence is being typed, it's aborted and the strokes that were swallowed for it are forwarded.
+    pub fn remove_sequence(&self, sequence: &KeySequence) -> bool {
+        let removed = self.update_set(|shortcuts| shortcuts.remove_sequence(sequence));
+        if removed {
+            self.signals.removed.notify_waiters();
+        }
+        removed
+    }
+
+    /// Check if a key sequence is currently being listened for
+    pub fn has_sequence(&self, sequence: &KeySequence) -> bool {
+        self.shortcuts.load().contains_sequence(sequence)
+    }
+
+    /// Returns `true` if the tap was not previously listened to
+    ///
+    /// Taps are reported by [listen_events](ShortcutListener::listen_events) as [Tap](ListenerEvent::Tap) events.
+    pub fn add_tap(&self, tap: Tap) -> bool {
+        self.update_set(|shortcuts| shortcuts.insert_tap(tap.clone()))
+    }
+
+    /// Returns `true` if the tap was previously listened to
+    pub fn remove_tap(&self, tap: &Tap) -> bool {
+        let removed = self.update_set(|shortcuts| shortcuts.remove_tap(tap));
+        if removed {
+            self.signals.removed.notify_waiters();
+        }
+        removed
+    }
+
+    /// Check if a tap is currently being listened for
+    pub fn has_tap(&self, tap: &Tap) -> bool {
+        self.shortcuts.load().contains_tap(tap)
+    }
+
+    /// Returns `true` if the chord was not previously listened to
+    ///
+    /// Chords are reported by [listen_events](ShortcutListener::listen_events) as [Chord](ListenerEvent::Chord) events.
+    ///
+    /// When [grabbing](ShortcutListener::set_grab), the keys of a chord are forwarded until the chord is complete,
+    /// the chord keys that are still held on the virtual keyboard are then released there.
+    ///
+    /// A chord that contains all keys of a chord that is already listened to, or the other way around, is rejected.
+    pub fn add_chord(&self, chord: Chord) -> Result<bool, ChordConflict> {
+        self.update_set(|shortcuts| shortcuts.insert_chord(chord.clone()))
+    }
+
+    /// Returns `true` if the chord was previously listened to
+    pub fn remove_chord(&self, chord: &Chord) -> bool {
+        let removed = self.update_set(|shortcuts| shortcuts.remove_chord(chord));
+        if removed {
+            self.signals.removed.notify_waiters();
+        }
+        removed
+    }
+
+    /// Check if a chord is currently being listened for
+    pub fn has_chord(&self, chord: &Chord) -> bool {
+        self.shortcuts.load().contains_chord(chord)
     }
 
     /// Returns `true` if the shortcut was previously listened to
     pub fn remove(&self, shortcut: &Shortcut) -> bool {
-        self.shortcuts.lock().unwrap().remove(shortcut)
+        let removed = self.update_set(|shortcuts| shortcuts.remove(shortcut));
+        if removed {
+            self.signals.removed.notify_waiters();
+        }
+        removed
+    }
+
+    /// Stop all streams of the listener
+    ///
+    /// Held shortcuts are released, with [ListenerStopped](crate::ReleaseReason::ListenerStopped) as reason,
+    /// after which the streams end.
+    pub fn stop(&self) {
+        self.signals.stops.fetch_add(1, Ordering::SeqCst);
+        self.signals.stop.notify_waiters();
     }
 
     /// Check if a shortcut is currently being listened for
     pub fn has(&self, shortcut: &Shortcut) -> bool {
-        self.shortcuts.lock().unwrap().contains(shortcut)
+        self.shortcuts.load().contains(shortcut)
+    }
+
+    /// Warn about and reject a shortcut that overlaps with the first stroke of a key sequence
+    fn reject_overlapping(&self, shortcut: &Shortcut) -> bool {
+        let shortcuts = self.shortcuts.load();
+        let Some(sequence) = shortcuts.overlapping_sequence(shortcut) else {
+            return false;
+        };
+        warn!(%shortcut, %sequence, "rejecting shortcut that overlaps with the first stroke of a key sequence");
+        true
+    }
+
+    /// Apply a change to a copy of the shortcuts and publish it, returning the result of the change
+    ///
+    /// The change can run more than once when the shortcuts are updated concurrently.
+    /// Active streams keep using their snapshot of the shortcuts until they handle the next key.
+    fn update_set<R>(&self, f: impl Fn(&mut ShortcutSet) -> R) -> R {
+        let mut result = None;
+        self.shortcuts.rcu(|shortcuts| {
+            let mut shortcuts = ShortcutSet::clone(shortcuts);
+            result = Some(f(&mut shortcuts));
+            shortcuts
+        });
+        result.expect("rcu applies the change at least once")
+    }
+}
+
+fn default_grab_release() -> Shortcut {
+    Shortcut::new(&[Modifier::LeftAlt, Modifier::LeftCtrl, Modifier::LeftShift], Key::KeyEsc)
+}
+
+/// Open a device, grabbing it if `released` is provided and the grab hasn't been released yet
+///
+/// The stream starts with the keys that are held on the device.
+/// If keys are held, the device is only grabbed once they are released.
+fn open_device(path: &Path, mut device: Device, released: Option<&Arc<AtomicBool>>) -> io::Result<Source> {
+    let info = Arc::new(device_info(path, &device));
+    let keys = device_key_state(&device).unwrap_or_else(|error| {
+        warn!(device = ?path, %error, "failed to read held keys");
+        KeySet::new()
+    });
+    let grab_now = keys.is_empty();
+    let key_state = iter([SourceEvent::KeyState { device: info.clone(), keys }]);
+    match released {
+        Some(_) if !can_grab(&device) => {
+            warn!(device = ?path, "not grabbing device that reports events other than keys");
+            Ok(key_state.chain(device_events(info, device.into_event_stream()?)).boxed())
+        }
+        Some(released) if !released.load(Ordering::Relaxed) => {
+            if grab_now {
+                device.grab()?;
+            }
+            Ok(key_state.chain(grabbed_device_events(info, device.into_event_stream()?, released.clone(), grab_now)).boxed())
+        }
+        _ => Ok(key_state.chain(device_events(info, device.into_event_stream()?)).boxed()),
+    }
+}
+
+/// Read the keys that are currently held on a device
+fn device_key_state(device: &Device) -> io::Result<KeySet> {
+    Ok(device
+        .get_key_state()?
+        .iter()
+        .filter_map(|key| Key::try_from(key.code()).ok())
+        .collect())
+}
+
+fn open_new_device(path: &Path, filters: &[DeviceFilter], released: Option<&Arc<AtomicBool>>) -> io::Result<Option<Source>> {
+    let device = Device::open(path)?;
+    // never listen to our own virtual keyboards, forwarded keys would be grabbed again
+    let matches = !is_own_device(device.name()) && device
+        .supported_keys()
+        .is_some_and(|keys| filters.iter().any(|filter| filter.matches(keys)));
+    if matches {
+        open_device(path, device, released).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Tag the events of a device with its path, ending the stream after the first error
+fn device_events<S>(device: Arc<DeviceInfo>, events: S) -> impl Stream<Item=SourceEvent>
+    where S: Stream<Item=io::Result<InputEvent>>
+{
+    stream! {
+        pin_mut!(events);
+
+        while let Some(event) = events.next().await {
+            let failed = event.is_err();
+            yield SourceEvent::Input { device: device.clone(), event, grabbed: false };
+            if failed {
+                break;
+            }
+        }
+    }
+}
+
+/// Watch a directory for new event device nodes
+fn watch_devices(dir: &Path) -> impl Stream<Item=SourceEvent> {
+    let dir = dir.to_path_buf();
+    stream! {
+        // device nodes are often created before udev sets their permissions,
+        // so attribute changes are watched to retry opening the ones that couldn't be opened
+        let events = Inotify::init().and_then(|inotify| {
+            inotify.watches().add(&dir, WatchMask::CREATE | WatchMask::ATTRIB)?;
+            inotify.into_event_stream([0; 1024])
+        });
+        let events = match events {
+            Ok(events) => events,
+            Err(error) => {
+                yield SourceEvent::WatchError(error);
+                return;
+            }
+        };
+
+        pin_mut!(events);
+
+        while let Some(event) = events.next().await {
+            match event {
+                Ok(event) => {
+                    let Some(name) = event.name else {
+                        continue;
+                    };
+                    if !name.to_string_lossy().starts_with("event") {
+                        continue;
+                    }
+                    if event.mask.contains(EventMask::CREATE) {
+                        yield SourceEvent::DeviceAppeared(dir.join(name));
+                    } else {
+                        yield SourceEvent::DeviceChanged(dir.join(name));
+                    }
+                }
+                Err(error) => {
+                    yield SourceEvent::WatchError(error);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Update the shortcuts of the matcher if they have been changed since the last event
+///
+/// Returns the release events of held shortcuts that have been removed,
+/// and the abort of a sequence that can no longer be completed, after forwarding the strokes swallowed for it.
+fn refresh_shortcuts(shortcuts: &ArcSwap<ShortcutSet>, matcher: &mut ShortcutMatcher, grab: &mut Option<Grab>) -> Vec<MatchEvent> {
+    let current = shortcuts.load();
+    if Arc::ptr_eq(&current, matcher.shortcuts()) {
+        return Vec::new();
+    }
+    let events: Vec<_> = matcher.set_shortcuts(Guard::into_inner(current)).collect();
+    replay_aborted(grab, &events);
+    events
+}
+
+/// Forward the strokes that were swallowed for a key sequence if the events abort it
+fn replay_aborted(grab: &mut Option<Grab>, events: &[MatchEvent]) {
+    let Some(grab) = grab else {
+        return;
+    };
+    let aborted = events.iter().any(|event| matches!(event, MatchEvent::Sequence(event) if event.state == SequenceState::Aborted));
+    if aborted {
+        grab.replay_strokes();
     }
 }
+
+/// What woke up a shortcut stream
+enum Wakeup {
+    Source(Option<SourceEvent>),
+    Deadline,
+    Removed,
+    Stop,
+}
+
+/// Turn a stream of raw input events into shortcut events
+///
+/// If a device opener is provided, newly appeared devices are opened and added to the sources.
+/// Once the sources end or the stream is stopped, the held shortcuts are released.
+fn shortcut_stream(
+    shortcuts: Arc<ArcSwap<ShortcutSet>>,
+    mut matcher: ShortcutMatcher,
+    mut sources: SelectAll<Source>,
+    mut open_devices: HashMap<PathBuf, Arc<Path>>,
+    opener: Option<DeviceOpener>,
+    mut grab: Option<Grab>,
+    signals: Arc<StreamSignals>,
+) -> impl Stream<Item=ListenerEvent> {
+    let stops = signals.stops.load(Ordering::SeqCst);
+    stream! {
+        // new devices that couldn't be opened because their permissions weren't set yet
+        let mut unreadable = HashSet::new();
+        loop {
+            let removed = signals.removed.notified();
+            let stop = signals.stop.notified();
+            pin_mut!(removed, stop);
+            // listen for the signals before checking the state, so no change is missed
+            removed.as_mut().enable();
+            stop.as_mut().enable();
+            if signals.stops.load(Ordering::SeqCst) != stops {
+                break;
+            }
+            for event in refresh_shortcuts(&shortcuts, &mut matcher, &mut grab) {
+                yield event.into();
+            }
+
+            let deadline = matcher.deadline();
+            let timeout = async move {
+                match deadline {
+                    Some(deadline) => tokio::time::sleep(deadline.duration_since(SystemTime::now()).unwrap_or_default()).await,
+                    None => future::pending().await,
+                }
+            };
+            let wakeup = tokio::select! {
+                biased;
+                _ = stop => Wakeup::Stop,
+                _ = removed => Wakeup::Removed,
+                source_event = sources.next() => Wakeup::Source(source_event),
+                _ = timeout => Wakeup::Deadline,
+            };
+            let source_event = match wakeup {
+                Wakeup::Source(Some(source_event)) => source_event,
+                Wakeup::Source(None) | Wakeup::Stop => break,
+                Wakeup::Removed => continue,
+                Wakeup::Deadline => {
+                    let events: Vec<_> = matcher.tick(SystemTime::now()).collect();
+                    replay_aborted(&mut grab, &events);
+                    for event in events {
+                        yield event.into();
+                    }
+                    continue;
+                }
+            };
+            let (device, event, grabbed) = match source_event {
+                SourceEvent::Input { device, event, grabbed } => (device, event, grabbed),
+                SourceEvent::KeyState { device, keys } => {
+                    for event in refresh_shortcuts(&shortcuts, &mut matcher, &mut grab) {
+                        yield event.into();
+                    }
+                    for event in matcher.sync_device_keys(Some(&device), &keys, SystemTime::now()) {
+                        yield event.into();
+                    }
+                    continue;
+                }
+                // only retry devices that couldn't be opened, not the ones that were rejected by the filters
+                SourceEvent::DeviceChanged(path) if !unreadable.contains(&path) => continue,
+                SourceEvent::DeviceAppeared(path) | SourceEvent::DeviceChanged(path) => {
+                    let Some(opener) = &opener else {
+                        continue;
+                    };
+                    let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+                    if open_devices.contains_key(&canonical) {
+                        continue;
+                    }
+                    let opened = opener(&path);
+                    if matches!(&opened, Err(error) if error.kind() == io::ErrorKind::PermissionDenied) {
+                        unreadable.insert(path.clone());
+                    } else {
+                        unreadable.remove(&path);
+                    }
+                    match opened {
+                        Ok(Some(source)) => {
+                            info!(device = ?path, "device added");
+                            open_devices.insert(canonical, path.as_path().into());
+                            sources.push(source);
+                            yield ListenerEvent::DeviceAdded(path);
+                        }
+                        Ok(None) => {
+                            debug!(device = ?path, "ignoring new device that doesn't match the hotplug filters");
+                        }
+                        Err(error) => {
+                            // permissions are often not set yet when the device is created
+                            debug!(device = ?path, %error, "failed to open new device");
+                        }
+                    }
+                    continue;
+                }
+                SourceEvent::WatchError(error) => {
+                    warn!(%error, "error while watching for new input devices");
+                    yield ListenerEvent::DeviceError(DeviceReadError {
+                        device: INPUT_DIR.into(),
+                        error: Arc::new(error),
+                    });
+                    continue;
+                }
+            };
+            let event = match event {
+                Ok(event) => event,
+                Err(error) => {
+                    open_devices.retain(|_, open| open.as_ref() != device.path);
+                    // the stream of the device ends after an error, so its keys will never be released
+                    for event in matcher.remove_device(&device, SystemTime::now()) {
+                        yield event.into();
+                    }
+                    if opener.is_some() && error.raw_os_error() == Some(ENODEV) {
+                        info!(device = ?device.path, "device removed");
+                        yield ListenerEvent::DeviceRemoved(device.path.clone());
+                        continue;
+                    }
+                    warn!(device = ?device.path, %error, "error while reading from input device");
+                    yield ListenerEvent::DeviceError(DeviceReadError {
+                        device: device.path.clone(),
+                        error: Arc::new(error),
+                    });
+                    continue;
+                }
+            };
+            trace!(device = ?device.path, ?event, "evdev event");
+            match DecodedEvent::from(&event) {
+                DecodedEvent::Key { key, value } => {
+                    for event in refresh_shortcuts(&shortcuts, &mut matcher, &mut grab) {
+                        yield event.into();
+                    }
+                    let time = event.timestamp();
+                    let events: Vec<_> = matcher.handle_device_key(Some(&device), key, value, time).collect();
+                    if let Some(grab) = &mut grab {
+                        // forward before emitting the events, to not delay typing while the events are handled
+                        let consumed = value == 1 && events.iter().any(|event| match event {
+                            MatchEvent::Shortcut(event) => event.state == ShortcutState::Pressed && event.shortcut.key() == Some(key),
+                            MatchEvent::Sequence(event) => event.state != SequenceState::Aborted,
+                            // taps trigger on release, after the press has been forwarded
+                            MatchEvent::Tap(_) => false,
+                            MatchEvent::Chord(event) => event.state == ShortcutState::Pressed,
+                        });
+                        let mut stroke = false;
+                        for event in &events {
+                            match event {
+                                MatchEvent::Chord(event) if event.state == ShortcutState::Pressed && grabbed => {
+                                    grab.swallow_held(event.chord.keys());
+                                }
+                                // the strokes are replayed before the key that aborted the sequence is forwarded
+                                MatchEvent::Sequence(event) => match event.state {
+                                    SequenceState::InProgress => stroke = true,
+                                    SequenceState::Completed => grab.clear_strokes(),
+                                    SequenceState::Aborted => grab.replay_strokes(),
+                                },
+                                _ => {}
+                            }
+                        }
+                        if grab.handle_key(&device, key, value, time, grabbed, consumed) {
+                            yield ListenerEvent::GrabReleased;
+                        }
+                        if stroke {
+                            grab.buffer_stroke(key);
+                        }
+                    }
+                    for event in events {
+                        yield event.into();
+                    }
+                }
+                DecodedEvent::UnknownKey { code, value } => {
+                    trace!(code, value, "unknown key");
+                    if let Some(grab) = &mut grab {
+                        grab.handle_unknown_key(&device, code, value, grabbed);
+                    }
+                }
+                DecodedEvent::Scancode(scancode) => {
+                    trace!(scancode, "scancode");
+                    if let Some(grab) = &mut grab {
+                        grab.handle_scancode(&device, scancode, grabbed);
+                    }
+                }
+                DecodedEvent::Led { led, on } => {
+                    trace!(?led, on, "led changed");
+                }
+                DecodedEvent::Switch { switch, on } => {
+                    trace!(?switch, on, "switch changed");
+                }
+                // SYN_DROPPED never gets here, the evdev event stream handles it by emitting
+                // key events for the keys that changed while events were dropped
+                DecodedEvent::Sync(_) | DecodedEvent::Other => {}
+            }
+        }
+        for event in matcher.release_all(SystemTime::now()) {
+            yield event.into();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evdev::EventType;
+    use futures::future::ready;
+    use futures::stream::pending;
+    use std::sync::Mutex;
+    use crate::{Key, Modifier, ReleaseReason};
+
+    fn test_device(path: &str) -> Arc<DeviceInfo> {
+        Arc::new(DeviceInfo::from(Path::new(path)))
+    }
+
+    fn key_events(device: &str, keys: &[(Key, i32)]) -> Source {
+        device_events(test_device(device), iter(keys.iter()
+            .map(|(key, value)| Ok(InputEvent::new(EventType::KEY, *key as u16, *value)))
+            .collect::<Vec<_>>())).boxed()
+    }
+
+    fn raw_events(events: &[(EventType, u16, i32)]) -> Source {
+        device_events(test_device("/dev/input/event0"), iter(events.iter()
+            .map(|(ty, code, value)| Ok(InputEvent::new(*ty, *code, *value)))
+            .collect::<Vec<_>>())).boxed()
+    }
+
+    fn shortcut_events(events: impl Stream<Item=ListenerEvent>) -> impl Stream<Item=ShortcutEvent> {
+        events.filter_map(|event| async move {
+            match event {
+                ListenerEvent::Shortcut(event) => Some(event),
+                _ => None,
+            }
+        })
+    }
+
+    fn listen(shortcuts: Arc<ArcSwap<ShortcutSet>>, sources: Vec<Source>) -> impl Stream<Item=ListenerEvent> {
+        shortcut_stream(shortcuts, ShortcutMatcher::new(), select_all(sources), HashMap::new(), None, None, Arc::default())
+    }
+
+    /// Collect the shortcut events, leaving out the releases caused by the end of the events
+    async fn collect_events(shortcuts: &[Shortcut], events: Source) -> Vec<(Shortcut, ShortcutState)> {
+        shortcut_events(listen(self::shortcuts(shortcuts), vec![events]))
+            .filter(|event| ready(event.reason != Some(ReleaseReason::ListenerStopped)))
+            .map(|event| (event.shortcut, event.state))
+            .collect()
+            .await
+    }
+
+    fn shortcuts(shortcuts: &[Shortcut]) -> Arc<ArcSwap<ShortcutSet>> {
+        Arc::new(ArcSwap::from_pointee(shortcuts.iter().cloned().collect()))
+    }
+
+    /// Change the shortcuts the way the listener does, by swapping in a modified copy
+    fn update(shortcuts: &ArcSwap<ShortcutSet>, f: impl Fn(&mut ShortcutSet)) {
+        shortcuts.rcu(|shortcuts| {
+            let mut shortcuts = ShortcutSet::clone(shortcuts);
+            f(&mut shortcuts);
+            shortcuts
+        });
+    }
+
+    #[tokio::test]
+    async fn events_from_all_devices_are_handled() {
+        let first = key_events("/dev/input/event0", &[(Key::KeyA, 1), (Key::KeyA, 0)]);
+        let second = key_events("/dev/input/event1", &[(Key::KeyB, 1), (Key::KeyB, 0)]);
+        let shortcuts = shortcuts(&[
+            Shortcut::new(&[], Key::KeyA),
+            Shortcut::new(&[], Key::KeyB),
+        ]);
+
+        let events: Vec<_> = shortcut_events(listen(shortcuts, vec![first, second]))
+            .map(|event| (event.shortcut.key(), event.state))
+            .collect()
+            .await;
+
+        for key in [Key::KeyA, Key::KeyB] {
+            assert!(events.contains(&(Some(key), ShortcutState::Pressed)));
+            assert!(events.contains(&(Some(key), ShortcutState::Released)));
+        }
+        assert_eq!(4, events.len());
+    }
+
+    #[tokio::test]
+    async fn idle_device_does_not_block_others() {
+        let idle = device_events(test_device("/dev/input/event0"), pending()).boxed();
+        let active = key_events("/dev/input/event1", &[(Key::KeyLeftMeta, 1), (Key::KeyN, 1), (Key::KeyN, 0)]);
+        let shortcuts = shortcuts(&[Shortcut::new(&[Modifier::Meta], Key::KeyN)]);
+
+        let events: Vec<_> = shortcut_events(listen(shortcuts, vec![idle, active]))
+            .map(|event| event.state)
+            .take(2)
+            .collect()
+            .await;
+
+        assert_eq!(vec![ShortcutState::Pressed, ShortcutState::Released], events);
+    }
+
+    #[tokio::test]
+    async fn led_events_are_not_key_presses() {
+        // EV_LED code 1 is the CapsLock led, EV_KEY code 1 is KeyEsc
+        let events = raw_events(&[
+            (EventType::LED, 1, 1),
+            (EventType::SYNCHRONIZATION, 0, 0),
+        ]);
+        assert!(collect_events(&[Shortcut::new(&[], Key::KeyEsc)], events).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn led_events_dont_release_keys() {
+        let esc = Shortcut::new(&[], Key::KeyEsc);
+        let events = raw_events(&[
+            (EventType::KEY, 1, 1),
+            (EventType::LED, 1, 0),
+            (EventType::SYNCHRONIZATION, 0, 0),
+        ]);
+        assert_eq!(vec![(esc.clone(), ShortcutState::Pressed)], collect_events(&[esc], events).await);
+    }
+
+    #[tokio::test]
+    async fn scancodes_and_sync_dont_affect_modifiers() {
+        // MSC_SCAN (code 4) and SYN_REPORT (code 0) would collide with Key3 and KeyReserved,
+        // EV_SW code 0 (the lid switch) would collide with KeyReserved as well
+        let shortcut = Shortcut::new(&[], Key::Key3);
+        let events = raw_events(&[
+            (EventType::MISC, 4, 0x70020),
+            (EventType::SWITCH, 0, 1),
+            (EventType::SYNCHRONIZATION, 0, 0),
+        ]);
+        assert!(collect_events(&[shortcut], events).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn non_key_events_dont_block_shortcuts() {
+        let shortcut = Shortcut::new(&[Modifier::Ctrl], Key::KeyP);
+        let events = raw_events(&[
+            (EventType::MISC, 4, 0x700e0),
+            (EventType::KEY, Key::KeyLeftCtrl as u16, 1),
+            (EventType::SYNCHRONIZATION, 0, 0),
+            (EventType::LED, 0, 1),
+            (EventType::MISC, 4, 0x70013),
+            (EventType::KEY, Key::KeyP as u16, 1),
+            (EventType::SYNCHRONIZATION, 0, 0),
+        ]);
+        assert_eq!(vec![(shortcut.clone(), ShortcutState::Pressed)], collect_events(&[shortcut], events).await);
+    }
+
+    #[tokio::test]
+    async fn held_keys_are_synchronized() {
+        let shortcut = Shortcut::new(&[Modifier::Ctrl], Key::KeyP);
+        let device = test_device("/dev/input/event0");
+        let held = iter(vec![SourceEvent::KeyState {
+            device: device.clone(),
+            keys: [Key::KeyLeftCtrl, Key::KeyP].into_iter().collect(),
+        }]);
+        let events = held.chain(device_events(device, iter(vec![
+            Ok(InputEvent::new(EventType::KEY, Key::KeyP as u16, 0)),
+        ]))).boxed();
+        assert_eq!(vec![
+            (shortcut.clone(), ShortcutState::Pressed),
+            (shortcut.clone(), ShortcutState::Released),
+        ], collect_events(&[shortcut], events).await);
+    }
+
+    #[tokio::test]
+    async fn removed_and_stopped_shortcuts_are_released() {
+        let listener = ShortcutListener::new();
+        let (first, second) = (Shortcut::new(&[], Key::KeyA), Shortcut::new(&[], Key::KeyB));
+        listener.add(first.clone());
+        listener.add(second.clone());
+        let held = key_events("/dev/input/event0", &[(Key::KeyA, 1), (Key::KeyB, 1)]);
+        let idle = device_events(test_device("/dev/input/event1"), pending()).boxed();
+        let events = shortcut_events(shortcut_stream(listener.shortcuts.clone(), ShortcutMatcher::new(), select_all([held, idle]), HashMap::new(), None, None, listener.signals.clone()))
+            .map(|event| (event.shortcut, event.state, event.reason));
+        pin_mut!(events);
+
+        assert_eq!(Some((first.clone(), ShortcutState::Pressed, None)), events.next().await);
+        assert_eq!(Some((second.clone(), ShortcutState::Pressed, None)), events.next().await);
+        listener.remove(&first);
+        assert_eq!(Some((first, ShortcutState::Released, Some(ReleaseReason::ShortcutRemoved))), events.next().await);
+        listener.stop();
+        assert_eq!(Some((second, ShortcutState::Released, Some(ReleaseReason::ListenerStopped))), events.next().await);
+        assert_eq!(None, events.next().await);
+    }
+
+    #[tokio::test]
+    async fn stop_after_key_event() {
+        let listener = ShortcutListener::new();
+        listener.add(Shortcut::new(&[], Key::KeyA));
+        let held = key_events("/dev/input/event0", &[(Key::KeyA, 1)]);
+        let idle = device_events(test_device("/dev/input/event1"), pending()).boxed();
+        let events = shortcut_events(shortcut_stream(listener.shortcuts.clone(), ShortcutMatcher::new(), select_all([held, idle]), HashMap::new(), None, None, listener.signals.clone()))
+            .map(|event| (event.state, event.reason));
+        pin_mut!(events);
+
+        assert_eq!(Some((ShortcutState::Pressed, None)), events.next().await);
+        listener.stop();
+        let released = tokio::time::timeout(Duration::from_secs(1), events.next()).await;
+        assert_eq!(Ok(Some((ShortcutState::Released, Some(ReleaseReason::ListenerStopped)))), released);
+        assert_eq!(None, events.next().await);
+    }
+
+    #[tokio::test]
+    async fn removed_and_stopped_chords_are_released() {
+        let listener = ShortcutListener::new();
+        let (first, second): (Chord, Chord) = ("KeyJ+KeyK".parse().unwrap(), "KeyA+KeyS".parse().unwrap());
+        listener.add_chord(first.clone()).unwrap();
+        listener.add_chord(second.clone()).unwrap();
+        let held = key_events("/dev/input/event0", &[(Key::KeyJ, 1), (Key::KeyK, 1), (Key::KeyA, 1), (Key::KeyS, 1)]);
+        let idle = device_events(test_device("/dev/input/event1"), pending()).boxed();
+        let events = shortcut_stream(listener.shortcuts.clone(), ShortcutMatcher::new(), select_all([held, idle]), HashMap::new(), None, None, listener.signals.clone())
+            .filter_map(|event| ready(match event {
+                ListenerEvent::Chord(event) => Some((event.chord, event.state)),
+                _ => None,
+            }));
+        pin_mut!(events);
+
+        assert_eq!(Some((first.clone(), ShortcutState::Pressed)), events.next().await);
+        assert_eq!(Some((second.clone(), ShortcutState::Pressed)), events.next().await);
+        listener.remove_chord(&first);
+        assert_eq!(Some((first, ShortcutState::Released)), events.next().await);
+        listener.stop();
+        assert_eq!(Some((second, ShortcutState::Released)), events.next().await);
+        assert_eq!(None, events.next().await);
+    }
+
+    #[test]
+    fn add_rejects_sequence_overlap() {
+        let listener = ShortcutListener::new();
+        listener.add_sequence("<Ctrl>-KeyK KeyB".parse().unwrap()).unwrap();
+        let shortcut: Shortcut = "<LeftCtrl>-KeyK".parse().unwrap();
+        assert!(!listener.add(shortcut.clone()));
+        assert!(!listener.add_with(shortcut.clone(), ShortcutOptions::default()));
+        assert!(!listener.has(&shortcut));
+        assert!(listener.add("<Ctrl>-KeyJ".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn removed_sequences_are_aborted() {
+        let listener = ShortcutListener::new();
+        let sequence: KeySequence = "KeyA KeyB".parse().unwrap();
+        listener.add_sequence(sequence.clone()).unwrap();
+        let typed = key_events("/dev/input/event0", &[(Key::KeyA, 1), (Key::KeyA, 0)]);
+        let idle = device_events(test_device("/dev/input/event1"), pending()).boxed();
+        let events = shortcut_stream(listener.shortcuts.clone(), ShortcutMatcher::new(), select_all([typed, idle]), HashMap::new(), None, None, listener.signals.clone())
+            .filter_map(|event| ready(match event {
+                ListenerEvent::Sequence(event) => Some(event.state),
+                _ => None,
+            }));
+        pin_mut!(events);
+
+        assert_eq!(Some(SequenceState::InProgress), events.next().await);
+        assert!(listener.remove_sequence(&sequence));
+        // aborted right away, not by the sequence timeout
+        let aborted = tokio::time::timeout(Duration::from_millis(500), events.next()).await;
+        assert_eq!(Ok(Some(SequenceState::Aborted)), aborted.map_err(drop));
+    }
+
+    #[tokio::test]
+    async fn device_errors_are_reported() {
+        let failing = device_events(test_device("/dev/input/event0"), iter(vec![
+            Ok(InputEvent::new(EventType::KEY, Key::KeyA as u16, 1)),
+            Err(io::Error::from_raw_os_error(19)),
+            Ok(InputEvent::new(EventType::KEY, Key::KeyA as u16, 0)),
+        ])).boxed();
+        let shortcuts = shortcuts(&[Shortcut::new(&[], Key::KeyA)]);
+
+        let events: Vec<_> = listen(shortcuts, vec![failing]).collect().await;
+
+        assert_eq!(3, events.len(), "events after the error should not be read");
+        assert!(matches!(&events[0], ListenerEvent::Shortcut(ShortcutEvent { state: ShortcutState::Pressed, .. })));
+        assert!(matches!(&events[1], ListenerEvent::Shortcut(ShortcutEvent {
+            state: ShortcutState::Released,
+            reason: Some(ReleaseReason::DeviceLost),
+            ..
+        })));
+        match &events[2] {
+            ListenerEvent::DeviceError(error) => {
+                assert_eq!(Path::new("/dev/input/event0"), error.device);
+                assert_eq!(Some(19), error.error.raw_os_error());
+            }
+            event => panic!("expected device error, got {:?}", event),
+        }
+    }
+
+    #[tokio::test]
+    async fn remaining_devices_keep_working_after_error() {
+        let failing = device_events(test_device("/dev/input/event0"), iter(vec![
+            Err(io::Error::from_raw_os_error(19)),
+        ])).boxed();
+        let working = key_events("/dev/input/event1", &[(Key::KeyB, 1), (Key::KeyB, 0)]);
+        let shortcuts = shortcuts(&[Shortcut::new(&[], Key::KeyB)]);
+
+        let events: Vec<_> = listen(shortcuts, vec![failing, working]).collect().await;
+
+        assert_eq!(1, events.iter().filter(|event| matches!(event, ListenerEvent::DeviceError(_))).count());
+        let states: Vec<_> = events.into_iter().filter_map(|event| match event {
+            ListenerEvent::Shortcut(event) => Some(event.state),
+            _ => None,
+        }).collect();
+        assert_eq!(vec![ShortcutState::Pressed, ShortcutState::Released], states);
+    }
+
+    fn open_fake_keyboard(path: &Path) -> io::Result<Option<Source>> {
+        match path.to_str() {
+            Some("/dev/input/event1") => Ok(Some(key_events("/dev/input/event1", &[(Key::KeyB, 1), (Key::KeyB, 0)]))),
+            Some("/dev/input/event2") => Ok(None),
+            _ => Err(io::Error::from_raw_os_error(13)),
+        }
+    }
+
+    #[tokio::test]
+    async fn hotplugged_devices_are_listened_to() {
+        let watcher = iter(vec![
+            SourceEvent::DeviceAppeared("/dev/input/event1".into()),
+            SourceEvent::DeviceAppeared("/dev/input/event2".into()),
+            SourceEvent::DeviceAppeared("/dev/input/event3".into()),
+        ]).boxed();
+        let shortcuts = shortcuts(&[Shortcut::new(&[], Key::KeyB)]);
+
+        let events: Vec<_> = shortcut_stream(shortcuts, ShortcutMatcher::new(), select_all([watcher]), HashMap::new(), Some(Box::new(open_fake_keyboard)), None, Arc::default())
+            .collect()
+            .await;
+
+        assert_eq!(3, events.len());
+        assert!(matches!(&events[0], ListenerEvent::DeviceAdded(path) if path == Path::new("/dev/input/event1")));
+        assert!(matches!(&events[1], ListenerEvent::Shortcut(ShortcutEvent { state: ShortcutState::Pressed, .. })));
+        assert!(matches!(&events[2], ListenerEvent::Shortcut(ShortcutEvent { state: ShortcutState::Released, .. })));
+    }
+
+    #[tokio::test]
+    async fn open_devices_are_not_reopened() {
+        let watcher = iter(vec![
+            SourceEvent::DeviceAppeared("/dev/input/event1".into()),
+        ]).boxed();
+        let open_devices = HashMap::from([(PathBuf::from("/dev/input/event1"), Path::new("/dev/input/by-id/kbd").into())]);
+
+        let events: Vec<_> = shortcut_stream(shortcuts(&[]), ShortcutMatcher::new(), select_all([watcher]), open_devices, Some(Box::new(open_fake_keyboard)), None, Arc::default())
+            .collect()
+            .await;
+
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn only_unreadable_devices_are_retried() {
+        let watcher = iter(vec![
+            SourceEvent::DeviceChanged("/dev/input/event1".into()),
+            SourceEvent::DeviceAppeared("/dev/input/event2".into()),
+            SourceEvent::DeviceChanged("/dev/input/event2".into()),
+            SourceEvent::DeviceAppeared("/dev/input/event3".into()),
+            SourceEvent::DeviceChanged("/dev/input/event3".into()),
+        ]).boxed();
+        let opened = Arc::new(Mutex::new(Vec::new()));
+        let opener = {
+            let opened = opened.clone();
+            move |path: &Path| {
+                opened.lock().unwrap().push(path.to_path_buf());
+                open_fake_keyboard(path)
+            }
+        };
+
+        shortcut_stream(shortcuts(&[]), ShortcutMatcher::new(), select_all([watcher]), HashMap::new(), Some(Box::new(opener)), None, Arc::default())
+            .collect::<Vec<_>>()
+            .await;
+
+        let expected: Vec<PathBuf> = vec!["/dev/input/event2".into(), "/dev/input/event3".into(), "/dev/input/event3".into()];
+        assert_eq!(expected, *opened.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn removed_devices_are_reported() {
+        let removed = device_events(test_device("/dev/input/event0"), iter(vec![
+            Ok(InputEvent::new(EventType::KEY, Key::KeyA as u16, 1)),
+            Err(io::Error::from_raw_os_error(ENODEV)),
+        ])).boxed();
+        let watcher = iter(vec![
+            SourceEvent::DeviceAppeared("/dev/input/event1".into()),
+        ]).boxed();
+        let shortcuts = shortcuts(&[Shortcut::new(&[], Key::KeyB)]);
+
+        let events: Vec<_> = shortcut_stream(shortcuts.clone(), ShortcutMatcher::new(), select_all([removed, watcher]), HashMap::new(), Some(Box::new(open_fake_keyboard)), None, Arc::default())
+            .collect()
+            .await;
+
+        assert!(events.iter().any(|event| matches!(event, ListenerEvent::DeviceRemoved(path) if path == Path::new("/dev/input/event0"))));
+        assert!(!events.iter().any(|event| matches!(event, ListenerEvent::DeviceError(_))));
+        // shortcuts keep working for the new device after the old one is removed
+        assert!(events.iter().any(|event| matches!(event, ListenerEvent::Shortcut(ShortcutEvent { state: ShortcutState::Released, .. }))));
+        assert!(shortcuts.load().contains(&Shortcut::new(&[], Key::KeyB)));
+    }
+
+    #[tokio::test]
+    async fn sequences_are_reported() {
+        let events = key_events("/dev/input/event0", &[
+            (Key::KeyLeftCtrl, 1),
+            (Key::KeyX, 1),
+            (Key::KeyX, 0),
+            (Key::KeyS, 1),
+            (Key::KeyS, 0),
+            (Key::KeyLeftCtrl, 0),
+        ]);
+        let sequence: KeySequence = "<Ctrl>-KeyX <Ctrl>-KeyS".parse().unwrap();
+        let shortcuts = shortcuts(&[]);
+        update(&shortcuts, |shortcuts| {
+            shortcuts.insert_sequence(sequence.clone()).unwrap();
+        });
+
+        let events: Vec<_> = listen(shortcuts, vec![events])
+            .filter_map(|event| async move {
+                match event {
+                    ListenerEvent::Sequence(event) => Some((event.sequence.to_string(), event.state)),
+                    _ => None,
+                }
+            })
+            .collect()
+            .await;
+
+        assert_eq!(vec![
+            ("<Ctrl>-KeyX".to_string(), SequenceState::InProgress),
+            (sequence.to_string(), SequenceState::Completed),
+        ], events);
+    }
+
+    #[tokio::test]
+    async fn chords_are_reported() {
+        let events = key_events("/dev/input/event0", &[
+            (Key::KeyK, 1),
+            (Key::KeyJ, 1),
+            (Key::KeyK, 0),
+            (Key::KeyJ, 0),
+        ]);
+        let chord: Chord = "KeyJ+KeyK".parse().unwrap();
+        let shortcuts = shortcuts(&[]);
+        update(&shortcuts, |shortcuts| {
+            shortcuts.insert_chord(chord.clone()).unwrap();
+        });
+
+        let events: Vec<_> = listen(shortcuts, vec![events])
+            .filter_map(|event| async move {
+                match event {
+                    ListenerEvent::Chord(event) => Some((event.chord, event.state)),
+                    _ => None,
+                }
+            })
+            .collect()
+            .await;
+
+        assert_eq!(vec![
+            (chord.clone(), ShortcutState::Pressed),
+            (chord, ShortcutState::Released),
+        ], events);
+    }
+
+    #[tokio::test]
+    async fn sequences_time_out_while_idle() {
+        let typed = key_events("/dev/input/event0", &[(Key::KeyA, 1), (Key::KeyA, 0)]);
+        let idle = device_events(test_device("/dev/input/event1"), pending()).boxed();
+        let shortcuts = shortcuts(&[]);
+        update(&shortcuts, |shortcuts| {
+            shortcuts.insert_sequence("KeyA KeyB".parse().unwrap()).unwrap();
+        });
+        let mut matcher = ShortcutMatcher::new();
+        matcher.set_sequence_timeout(Duration::from_millis(10));
+
+        let states: Vec<_> = shortcut_stream(shortcuts, matcher, select_all([typed, idle]), HashMap::new(), None, None, Arc::default())
+            .filter_map(|event| async move {
+                match event {
+                    ListenerEvent::Sequence(event) => Some(event.state),
+                    _ => None,
+                }
+            })
+            .take(2)
+            .collect()
+            .await;
+
+        assert_eq!(vec![SequenceState::InProgress, SequenceState::Aborted], states);
+    }
+
+    #[tokio::test]
+    async fn held_shortcuts_are_reported_while_idle() {
+        let typed = key_events("/dev/input/event0", &[(Key::KeyLeftCtrl, 1), (Key::KeyQ, 1)]);
+        let idle = device_events(test_device("/dev/input/event1"), pending()).boxed();
+        let shortcuts = shortcuts(&[]);
+        update(&shortcuts, |shortcuts| {
+            shortcuts.insert_with(Shortcut::new(&[Modifier::Ctrl], Key::KeyQ), ShortcutOptions {
+                hold: Some(Duration::from_millis(10)),
+                ..ShortcutOptions::default()
+            });
+        });
+
+        let states: Vec<_> = shortcut_events(listen(shortcuts, vec![typed, idle]))
+            .map(|event| event.state)
+            .take(2)
+            .collect()
+            .await;
+
+        assert_eq!(vec![ShortcutState::Pressed, ShortcutState::Held], states);
+    }
+
+    #[tokio::test]
+    async fn events_include_metadata() {
+        let events = device_events(test_device("/dev/input/event3"), iter(vec![
+            Ok(InputEvent::new(EventType::KEY, Key::KeyLeftCtrl as u16, 1)),
+            Ok(InputEvent::new(EventType::KEY, Key::KeyP as u16, 1)),
+            Ok(InputEvent::new(EventType::KEY, Key::KeyP as u16, 0)),
+        ])).boxed();
+        let shortcuts = shortcuts(&[Shortcut::new(&[Modifier::Ctrl], Key::KeyP)]);
+
+        let events: Vec<_> = shortcut_events(listen(shortcuts, vec![events])).collect().await;
+
+        assert_eq!(2, events.len());
+        for event in &events {
+            assert_eq!(Some(Path::new("/dev/input/event3")), event.device.as_ref().map(|device| device.path.as_path()));
+        }
+        assert_eq!(None, events[0].held_for);
+        assert!(events[1].held_for.is_some());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,2036 @@
+use crate::{find_conflicts, Chord, ChordConflict, ChordEvent, DeviceInfo, Key, KeySequence, KeySet, MatchPolicy, Modifier, Repeat, SequenceConflict, SequenceEvent, SequenceState, Shortcut, ShortcutConflict, ShortcutEvent, ShortcutOptions, ReleaseReason, ShortcutState, Tap, TapEvent, Trigger};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::{info, trace};
+
+/// Default time allowed between the strokes of a key sequence
+const DEFAULT_SEQUENCE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Default time a key can be held for to still count as a tap
+const DEFAULT_TAP_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Default time allowed between consecutive taps of a multi-tap
+const DEFAULT_MULTI_TAP_WINDOW: Duration = Duration::from_millis(250);
+
+/// Default time allowed between the first and last key press of a chord
+const DEFAULT_CHORD_WINDOW: Duration = Duration::from_millis(50);
+
+/// Set of shortcuts, key sequences, taps and chords, indexed by their key
+///
+/// Shortcuts are kept in the order they were added.
+#[derive(Debug, Clone, Default)]
+pub struct ShortcutSet {
+    shortcuts: Vec<Shortcut>,
+    /// Options of the shortcuts, by position
+    options: Vec<ShortcutOptions>,
+    positions: HashMap<Shortcut, usize>,
+    /// Positions of the shortcuts, indexed by key code
+    by_key: Vec<Vec<usize>>,
+    /// Positions of the shortcuts without key
+    modifier_only: Vec<usize>,
+    /// Keys that are used as custom modifier by any of the shortcuts
+    custom_modifiers: KeySet,
+    sequences: Vec<KeySequence>,
+    taps: Vec<Tap>,
+    chords: Vec<Chord>,
+}
+
+impl ShortcutSet {
+    pub fn new() -> Self {
+        ShortcutSet::default()
+    }
+
+    /// Returns `true` if the shortcut was not previously in the set
+    ///
+    /// A shortcut that overlaps with the first stroke of a key sequence in the set is rejected.
+    pub fn insert(&mut self, shortcut: Shortcut) -> bool {
+        if self.contains(&shortcut) {
+            return false;
+        }
+        self.insert_with(shortcut, ShortcutOptions::default())
+    }
+
+    /// Returns `true` if the shortcut was not previously in the set
+    ///
+    /// If the shortcut was already in the set, its options are replaced.
+    /// A shortcut that overlaps with the first stroke of a key sequence in the set is rejected.
+    pub fn insert_with(&mut self, shortcut: Shortcut, options: ShortcutOptions) -> bool {
+        if let Some(position) = self.position(&shortcut) {
+            self.options[position] = options;
+            return false;
+        }
+        if self.overlapping_sequence(&shortcut).is_some() {
+            return false;
+        }
+        let index = self.shortcuts.len();
+        match shortcut.key() {
+            Some(key) => {
+                let code = key as usize;
+                if self.by_key.len() <= code {
+                    self.by_key.resize(code + 1, Vec::new());
+                }
+                self.by_key[code].push(index);
+            }
+            None => self.modifier_only.push(index),
+        }
+        self.custom_modifiers.extend(shortcut.modifiers.custom_keys());
+        self.positions.insert(shortcut.clone(), index);
+        self.shortcuts.push(shortcut);
+        self.options.push(options);
+        true
+    }
+
+    /// Returns `true` if the shortcut was previously in the set
+    pub fn remove(&mut self, shortcut: &Shortcut) -> bool {
+        if !self.contains(shortcut) {
+            return false;
+        }
+        let shortcuts = std::mem::take(&mut self.shortcuts);
+        let options = std::mem::take(&mut self.options);
+        self.positions.clear();
+        self.by_key.clear();
+        self.modifier_only.clear();
+        self.custom_modifiers.clear();
+        for (existing, options) in shortcuts.into_iter().zip(options) {
+            if &existing != shortcut {
+                self.insert_with(existing, options);
+            }
+        }
+        true
+    }
+
+    /// Returns `true` if the sequence was not previously in the set
+    ///
+    /// Sequences that can't be told apart from a registered sequence while typing are rejected,
+    /// as are sequences whose first stroke is registered as a shortcut.
+    pub fn insert_sequence(&mut self, sequence: KeySequence) -> Result<bool, SequenceConflict> {
+        if self.contains_sequence(&sequence) {
+            return Ok(false);
+        }
+        if let Some(shortcut) = sequence.steps.first().and_then(|first| self.overlapping(first)) {
+            return Err(SequenceConflict::Shortcut {
+                shortcut: shortcut.clone(),
+                sequence,
+            });
+        }
+        if let Some(existing) = self.sequences.iter().find(|existing| existing.is_ambiguous_with(&sequence)) {
+            return Err(SequenceConflict::Sequence {
+                existing: existing.clone(),
+                sequence,
+            });
+        }
+        self.sequences.push(sequence);
+        Ok(true)
+    }
+
+    /// Returns `true` if the sequence was previously in the set
+    pub fn remove_sequence(&mut self, sequence: &KeySequence) -> bool {
+        let count = self.sequences.len();
+        self.sequences.retain(|existing| existing != sequence);
+        self.sequences.len() != count
+    }
+
+    pub fn contains_sequence(&self, sequence: &KeySequence) -> bool {
+        self.sequences.contains(sequence)
+    }
+
+    /// Iterate over the sequences in the order they were added
+    pub fn sequences(&self) -> impl Iterator<Item=&KeySequence> {
+        self.sequences.iter()
+    }
+
+    /// Returns `true` if the tap was not previously in the set
+    pub fn insert_tap(&mut self, tap: Tap) -> bool {
+        if self.contains_tap(&tap) {
+            return false;
+        }
+        self.taps.push(tap);
+        true
+    }
+
+    /// Returns `true` if the tap was previously in the set
+    pub fn remove_tap(&mut self, tap: &Tap) -> bool {
+        let count = self.taps.len();
+        self.taps.retain(|existing| existing != tap);
+        self.taps.len() != count
+    }
+
+    pub fn contains_tap(&self, tap: &Tap) -> bool {
+        self.taps.contains(tap)
+    }
+
+    /// Iterate over the taps in the order they were added
+    pub fn taps(&self) -> impl Iterator<Item=&Tap> {
+        self.taps.iter()
+    }
+
+    /// Returns `true` if the chord was not previously in the set
+    ///
+    /// Chords that contain all keys of a registered chord, or of which a registered chord contains all keys, are rejected.
+    pub fn insert_chord(&mut self, chord: Chord) -> Result<bool, ChordConflict> {
+        if self.contains_chord(&chord) {
+            return Ok(false);
+        }
+        if let Some(existing) = self.chords.iter().find(|existing| existing.includes(&chord) || chord.includes(existing)) {
+            return Err(ChordConflict {
+                existing: existing.clone(),
+                chord,
+            });
+        }
+        self.chords.push(chord);
+        Ok(true)
+    }
+
+    /// Returns `true` if the chord was previously in the set
+    pub fn remove_chord(&mut self, chord: &Chord) -> bool {
+        let count = self.chords.len();
+        self.chords.retain(|existing| existing != chord);
+        self.chords.len() != count
+    }
+
+    pub fn contains_chord(&self, chord: &Chord) -> bool {
+        self.chords.contains(chord)
+    }
+
+    /// Iterate over the chords in the order they were added
+    pub fn chords(&self) -> impl Iterator<Item=&Chord> {
+        self.chords.iter()
+    }
+
+    /// The first registered shortcut that can be triggered by the same keys as the provided shortcut
+    pub fn overlapping(&self, shortcut: &Shortcut) -> Option<&Shortcut> {
+        self.for_key(shortcut.key())
+            .iter()
+            .map(|position| &self.shortcuts[*position])
+            .find(|existing| existing.overlaps(shortcut))
+    }
+
+    /// The first registered sequence that starts with a stroke that can be triggered by the same keys as the provided shortcut
+    pub fn overlapping_sequence(&self, shortcut: &Shortcut) -> Option<&KeySequence> {
+        self.sequences
+            .iter()
+            .find(|sequence| sequence.steps.first().is_some_and(|first| first.overlaps(shortcut)))
+    }
+
+    /// Find the conflicts between the shortcuts in the set, see [find_conflicts]
+    pub fn conflicts(&self, most_specific_wins: bool) -> Vec<ShortcutConflict> {
+        find_conflicts(&self.shortcuts, most_specific_wins)
+    }
+
+    /// Returns `true` if the shortcut was not previously in the set
+    ///
+    /// A shortcut that [conflicts](find_conflicts) with a shortcut in the set, can never trigger,
+    /// or overlaps with the first stroke of a key sequence is rejected.
+    pub fn insert_checked(&mut self, shortcut: Shortcut, most_specific_wins: bool) -> Result<bool, ShortcutConflict> {
+        if self.contains(&shortcut) {
+            return Ok(false);
+        }
+        if let Some(sequence) = self.overlapping_sequence(&shortcut) {
+            return Err(ShortcutConflict::Sequence {
+                sequence: sequence.clone(),
+                shortcut,
+            });
+        }
+        let conflict = find_conflicts(self.shortcuts.iter().chain([&shortcut]), most_specific_wins)
+            .into_iter()
+            .find(|conflict| conflict.involves(&shortcut));
+        match conflict {
+            Some(conflict) => Err(conflict),
+            None => Ok(self.insert(shortcut)),
+        }
+    }
+
+    pub fn contains(&self, shortcut: &Shortcut) -> bool {
+        self.positions.contains_key(shortcut)
+    }
+
+    /// Position of the shortcut in the set
+    pub fn position(&self, shortcut: &Shortcut) -> Option<usize> {
+        self.positions.get(shortcut).copied()
+    }
+
+    /// Options of the shortcut, if it's in the set
+    pub fn options(&self, shortcut: &Shortcut) -> Option<&ShortcutOptions> {
+        self.position(shortcut).map(|position| &self.options[position])
+    }
+
+    /// Number of shortcuts in the set, not counting sequences, taps and chords
+    pub fn shortcut_count(&self) -> usize {
+        self.shortcuts.len()
+    }
+
+    /// Check if the set has no shortcuts, sequences, taps or chords
+    pub fn is_empty(&self) -> bool {
+        self.shortcuts.is_empty() && self.sequences.is_empty() && self.taps.is_empty() && self.chords.is_empty()
+    }
+
+    /// Iterate over the shortcuts in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item=&Shortcut> {
+        self.shortcuts.iter()
+    }
+
+    /// Check if the key is used as custom modifier by any of the shortcuts
+    pub fn is_custom_modifier(&self, key: Key) -> bool {
+        self.custom_modifiers.contains(key)
+    }
+
+    /// Positions of the shortcuts for a key, or of the modifier-only shortcuts
+    fn for_key(&self, key: Option<Key>) -> &[usize] {
+        match key {
+            Some(key) => self.by_key.get(key as usize).map(Vec::as_slice).unwrap_or_default(),
+            None => &self.modifier_only,
+        }
+    }
+}
+
+impl FromIterator<Shortcut> for ShortcutSet {
+    fn from_iter<T: IntoIterator<Item=Shortcut>>(iter: T) -> Self {
+        let mut set = ShortcutSet::new();
+        for shortcut in iter {
+            set.insert(shortcut);
+        }
+        set
+    }
+}
+
+/// Keys held on a single device
+#[derive(Debug, Clone, Default)]
+struct DeviceKeys {
+    keys: KeySet,
+    modifier_mask: u16,
+}
+
+impl DeviceKeys {
+    /// Returns `true` if the held keys changed
+    fn update(&mut self, key: Key, value: i32) -> bool {
+        let modifier = Modifier::mask_from_key(key);
+        match value {
+            1 => {
+                self.modifier_mask |= modifier;
+                self.keys.insert(key)
+            }
+            0 => {
+                self.modifier_mask &= !modifier;
+                self.keys.remove(key)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A triggered shortcut, for shortcuts that trigger on release it's armed until the keys are released
+#[derive(Debug, Clone)]
+struct Press {
+    time: SystemTime,
+    device: Option<Arc<DeviceInfo>>,
+    /// Whether another key was pressed while the shortcut was held
+    intervened: bool,
+}
+
+/// Consecutive taps of a key that might still continue
+#[derive(Debug, Clone)]
+struct TapStreak {
+    key: Key,
+    count: u8,
+    deadline: SystemTime,
+}
+
+/// Event emitted by a [ShortcutMatcher]
+#[derive(Debug, Clone)]
+pub enum MatchEvent {
+    Shortcut(ShortcutEvent),
+    Sequence(SequenceEvent),
+    Tap(TapEvent),
+    Chord(ChordEvent),
+}
+
+/// Tracks key presses and emits events for the registered shortcuts, key sequences, taps and chords
+///
+/// The matcher doesn't depend on evdev and can be fed key events from any source.
+///
+/// Only the shortcuts for the held keys are checked when handling a key,
+/// so the cost of handling a key event doesn't grow with the total number of registered shortcuts.
+///
+/// When a key event changes the state of multiple shortcuts, all releases are emitted before the presses,
+/// both in the order the shortcuts were registered.
+///
+/// Key sequences time out when the next stroke isn't typed within the [sequence timeout](ShortcutMatcher::set_sequence_timeout),
+/// multi-taps trigger once the [multi-tap window](ShortcutMatcher::set_multi_tap_window) closes
+/// shortcuts with a [hold threshold](ShortcutOptions::hold) are held once it passes
+/// and shortcuts with a [repeat rate](Repeat::Rate) repeat while held.
+/// Call [tick](ShortcutMatcher::tick) once the [deadline](ShortcutMatcher::deadline) has passed to get notified of them.
+///
+/// Example:
+///
+/// ```rust
+/// # use evdev_shortcut::{MatchEvent, ShortcutMatcher, Shortcut, ShortcutState, Modifier, Key};
+/// # use std::time::SystemTime;
+/// let mut matcher = ShortcutMatcher::new();
+/// matcher.add(Shortcut::new(&[Modifier::Ctrl], Key::KeyP));
+///
+/// assert_eq!(0, matcher.handle_key(Key::KeyLeftCtrl, 1, SystemTime::now()).count());
+/// let events: Vec<_> = matcher.handle_key(Key::KeyP, 1, SystemTime::now()).collect();
+/// assert!(matches!(&events[0], MatchEvent::Shortcut(event) if event.state == ShortcutState::Pressed));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ShortcutMatcher {
+    shortcuts: Arc<ShortcutSet>,
+    active_keys: KeySet,
+    modifier_mask: u16,
+    /// Press state of the shortcuts in the set, by position
+    pressed: Vec<Option<Press>>,
+    /// Whether the shortcuts were released by an extra modifier while their key is still held, by position
+    interrupted: Vec<bool>,
+    policy: MatchPolicy,
+    most_specific_wins: bool,
+    /// Device of the key event that is being handled
+    current_device: Option<Arc<DeviceInfo>>,
+    /// Whether the key event that is being handled is a key release
+    current_release: bool,
+    /// Whether only the shortcuts scoped to the current device are updated,
+    /// for key events that change the keys of a device but not the keys held overall
+    current_scoped_only: bool,
+    /// Reason for the releases caused by the key event that is being handled
+    current_reason: ReleaseReason,
+    /// Shortcut events caused by the key event that is being handled,
+    /// with whether they come from a press and the position of the shortcut
+    changes: Vec<(bool, usize, MatchEvent)>,
+    /// Keys held per device, by device path
+    device_keys: HashMap<PathBuf, DeviceKeys>,
+    /// Pressed shortcuts that haven't reached their hold threshold yet, with the time they reach it
+    pending_holds: Vec<(Shortcut, SystemTime)>,
+    /// Pressed shortcuts with a repeat rate, with the time of their next repeat and the repeat interval
+    pending_repeats: Vec<(Shortcut, SystemTime, Duration)>,
+    sequence_timeout: Duration,
+    /// Strokes of the key sequence that is being typed
+    sequence_progress: Vec<Shortcut>,
+    sequence_deadline: Option<SystemTime>,
+    tap_timeout: Duration,
+    /// Key that was pressed on its own, with the time it was pressed
+    tap_candidate: Option<(Key, SystemTime)>,
+    multi_tap_window: Duration,
+    tap_streak: Option<TapStreak>,
+    chord_window: Duration,
+    /// Held keys that are part of a chord, with the time they were pressed
+    chord_presses: Vec<(Key, SystemTime)>,
+    /// Chords that are pressed
+    active_chords: Vec<Chord>,
+    events: Vec<MatchEvent>,
+}
+
+impl Default for ShortcutMatcher {
+    fn default() -> Self {
+        ShortcutMatcher {
+            shortcuts: Arc::default(),
+            active_keys: KeySet::default(),
+            modifier_mask: 0,
+            pressed: Vec::new(),
+            interrupted: Vec::new(),
+            policy: MatchPolicy::default(),
+            most_specific_wins: false,
+            current_device: None,
+            current_release: false,
+            current_scoped_only: false,
+            current_reason: ReleaseReason::KeyReleased,
+            changes: Vec::new(),
+            device_keys: HashMap::new(),
+            pending_holds: Vec::new(),
+            pending_repeats: Vec::new(),
+            sequence_timeout: DEFAULT_SEQUENCE_TIMEOUT,
+            sequence_progress: Vec::new(),
+            sequence_deadline: None,
+            tap_timeout: DEFAULT_TAP_TIMEOUT,
+            tap_candidate: None,
+            multi_tap_window: DEFAULT_MULTI_TAP_WINDOW,
+            tap_streak: None,
+            chord_window: DEFAULT_CHORD_WINDOW,
+            chord_presses: Vec::new(),
+            active_chords: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+}
+
+impl ShortcutMatcher {
+    pub fn new() -> Self {
+        ShortcutMatcher::default()
+    }
+
+    /// Returns `true` if the shortcut was not previously registered
+    ///
+    /// A shortcut that overlaps with the first stroke of a registered key sequence is rejected.
+    pub fn add(&mut self, shortcut: Shortcut) -> bool {
+        let added = Arc::make_mut(&mut self.shortcuts).insert(shortcut);
+        self.pressed.resize(self.shortcuts.shortcut_count(), None);
+        self.interrupted.resize(self.shortcuts.shortcut_count(), false);
+        added
+    }
+
+    /// Returns `true` if the shortcut was not previously registered
+    ///
+    /// If the shortcut was already registered, its options are replaced.
+    /// A shortcut that overlaps with the first stroke of a registered key sequence is rejected.
+    pub fn add_with(&mut self, shortcut: Shortcut, options: ShortcutOptions) -> bool {
+        let added = Arc::make_mut(&mut self.shortcuts).insert_with(shortcut, options);
+        self.pressed.resize(self.shortcuts.shortcut_count(), None);
+        self.interrupted.resize(self.shortcuts.shortcut_count(), false);
+        added
+    }
+
+    /// Returns `true` if the shortcut was previously registered
+    ///
+    /// If the shortcut is pressed, its release is returned by the next call to [tick](ShortcutMatcher::tick)
+    /// or [handle_key](ShortcutMatcher::handle_key).
+    pub fn remove(&mut self, shortcut: &Shortcut) -> bool {
+        let mut shortcuts = self.shortcuts.clone();
+        let removed = Arc::make_mut(&mut shortcuts).remove(shortcut);
+        if removed {
+            self.replace_shortcuts(shortcuts, SystemTime::now());
+        }
+        removed
+    }
+
+    /// Check if a shortcut is registered
+    pub fn has(&self, shortcut: &Shortcut) -> bool {
+        self.shortcuts.contains(shortcut)
+    }
+
+    /// Returns `true` if the sequence was not previously registered
+    pub fn add_sequence(&mut self, sequence: KeySequence) -> Result<bool, SequenceConflict> {
+        Arc::make_mut(&mut self.shortcuts).insert_sequence(sequence)
+    }
+
+    /// Returns `true` if the sequence was previously registered
+    ///
+    /// If the sequence was being typed and no other sequence continues the typed strokes,
+    /// the sequence is aborted with the next call to [tick](ShortcutMatcher::tick) or [handle_key](ShortcutMatcher::handle_key).
+    pub fn remove_sequence(&mut self, sequence: &KeySequence) -> bool {
+        let removed = Arc::make_mut(&mut self.shortcuts).remove_sequence(sequence);
+        self.prune_sequence();
+        removed
+    }
+
+    /// Set the matching policy for shortcuts that don't have their own policy
+    pub fn set_policy(&mut self, policy: MatchPolicy) {
+        self.policy = policy;
+    }
+
+    /// When enabled, only the most specific of the shortcuts that trigger on the same key event triggers
+    ///
+    /// With `<Ctrl>-KeyP` and `<LeftCtrl>-KeyP` registered, pressing left control and `P` only triggers `<LeftCtrl>-KeyP`.
+    /// See [is_more_specific_than](Shortcut::is_more_specific_than).
+    pub fn set_most_specific_wins(&mut self, enabled: bool) {
+        self.most_specific_wins = enabled;
+    }
+
+    /// Set the time allowed between the strokes of a key sequence, defaults to one second
+    pub fn set_sequence_timeout(&mut self, timeout: Duration) {
+        self.sequence_timeout = timeout;
+    }
+
+    /// Returns `true` if the tap was not previously registered
+    pub fn add_tap(&mut self, tap: Tap) -> bool {
+        Arc::make_mut(&mut self.shortcuts).insert_tap(tap)
+    }
+
+    /// Returns `true` if the tap was previously registered
+    ///
+    /// A multi-tap that can no longer continue into a registered tap is finished right away.
+    pub fn remove_tap(&mut self, tap: &Tap) -> bool {
+        let removed = Arc::make_mut(&mut self.shortcuts).remove_tap(tap);
+        self.prune_tap_streak();
+        removed
+    }
+
+    /// Set the maximum time a key can be held for to still count as a tap, defaults to 200ms
+    pub fn set_tap_timeout(&mut self, timeout: Duration) {
+        self.tap_timeout = timeout;
+    }
+
+    /// Set the time allowed between the release of a tap and the press of the next tap of a multi-tap, defaults to 250ms
+    pub fn set_multi_tap_window(&mut self, window: Duration) {
+        self.multi_tap_window = window;
+    }
+
+    /// Returns `true` if the chord was not previously registered
+    ///
+    /// A chord that overlaps with a registered chord is rejected, see [ShortcutSet::insert_chord].
+    pub fn add_chord(&mut self, chord: Chord) -> Result<bool, ChordConflict> {
+        Arc::make_mut(&mut self.shortcuts).insert_chord(chord)
+    }
+
+    /// Returns `true` if the chord was previously registered
+    ///
+    /// If the chord is pressed, its release is returned by the next call to [tick](ShortcutMatcher::tick)
+    /// or [handle_key](ShortcutMatcher::handle_key).
+    pub fn remove_chord(&mut self, chord: &Chord) -> bool {
+        let removed = Arc::make_mut(&mut self.shortcuts).remove_chord(chord);
+        if let Some(index) = self.active_chords.iter().position(|active| active == chord) {
+            let chord = self.active_chords.remove(index);
+            self.release_chords(vec![chord]);
+        }
+        removed
+    }
+
+    /// Set the time allowed between the first and the last key press of a chord, defaults to 50ms
+    pub fn set_chord_window(&mut self, window: Duration) {
+        self.chord_window = window;
+    }
+
+    /// Replace all registered shortcuts
+    ///
+    /// Shortcuts that are part of both the old and new set keep their pressed state,
+    /// returning the release events of the pressed shortcuts that are not part of the new set.
+    pub fn set_shortcuts(&mut self, shortcuts: Arc<ShortcutSet>) -> impl Iterator<Item=MatchEvent> + '_ {
+        self.replace_shortcuts(shortcuts, SystemTime::now());
+        self.events.drain(..)
+    }
+
+    fn replace_shortcuts(&mut self, shortcuts: Arc<ShortcutSet>, time: SystemTime) {
+        self.current_reason = ReleaseReason::ShortcutRemoved;
+        for position in 0..self.pressed.len() {
+            if !shortcuts.contains(&self.shortcuts.shortcuts[position]) {
+                self.release(position, time, false);
+            }
+        }
+        self.current_reason = ReleaseReason::KeyReleased;
+        self.flush_changes();
+
+        let pressed = shortcuts
+            .iter()
+            .map(|shortcut| {
+                self.shortcuts
+                    .position(shortcut)
+                    .and_then(|position| self.pressed[position].clone())
+            })
+            .collect();
+        self.interrupted = shortcuts
+            .iter()
+            .map(|shortcut| {
+                self.shortcuts
+                    .position(shortcut)
+                    .is_some_and(|position| self.interrupted[position])
+            })
+            .collect();
+        self.pressed = pressed;
+        self.pending_holds.retain(|(shortcut, _)| shortcuts.contains(shortcut));
+        self.pending_repeats.retain(|(shortcut, _, _)| shortcuts.contains(shortcut));
+        let (active, removed) = std::mem::take(&mut self.active_chords)
+            .into_iter()
+            .partition(|chord| shortcuts.contains_chord(chord));
+        self.active_chords = active;
+        self.release_chords(removed);
+        self.shortcuts = shortcuts;
+        self.prune_sequence();
+        self.prune_tap_streak();
+    }
+
+    /// The registered shortcuts
+    pub fn shortcuts(&self) -> &Arc<ShortcutSet> {
+        &self.shortcuts
+    }
+
+    /// The keys that are currently held down
+    pub fn active_keys(&self) -> &KeySet {
+        &self.active_keys
+    }
+
+    /// The time at which [tick](ShortcutMatcher::tick) has to be called to handle the next timeout
+    pub fn deadline(&self) -> Option<SystemTime> {
+        let tap_deadline = self.tap_streak.as_ref().map(|streak| streak.deadline);
+        let hold_deadlines = self.pending_holds.iter().map(|(_, deadline)| *deadline);
+        let repeat_deadlines = self.pending_repeats.iter().map(|(_, next, _)| *next);
+        [self.sequence_deadline, tap_deadline]
+            .into_iter()
+            .flatten()
+            .chain(hold_deadlines)
+            .chain(repeat_deadlines)
+            .min()
+    }
+
+    /// Release all pressed shortcuts and chords, for when no more key events will be handled
+    ///
+    /// The shortcut release events have [ListenerStopped](ReleaseReason::ListenerStopped) as reason.
+    pub fn release_all(&mut self, time: SystemTime) -> impl Iterator<Item=MatchEvent> + '_ {
+        self.current_reason = ReleaseReason::ListenerStopped;
+        for position in 0..self.pressed.len() {
+            self.release(position, time, false);
+        }
+        self.current_reason = ReleaseReason::KeyReleased;
+        self.flush_changes();
+        let chords = std::mem::take(&mut self.active_chords);
+        self.release_chords(chords);
+        self.events.drain(..)
+    }
+
+    /// Handle the passing of time, returning the events for any timeouts that expired
+    pub fn tick(&mut self, now: SystemTime) -> impl Iterator<Item=MatchEvent> + '_ {
+        self.expire(now);
+        self.events.drain(..)
+    }
+
+    /// Handle a key event, returning the shortcut and sequence events it caused
+    ///
+    /// The value follows the evdev conventions: 0 for release, 1 for press and 2 for repeat.
+    pub fn handle_key(&mut self, key: Key, value: i32, time: SystemTime) -> impl Iterator<Item=MatchEvent> + '_ {
+        self.handle_device_key(None, key, value, time)
+    }
+
+    /// Handle a key event from a device, returning the shortcut and sequence events it caused
+    ///
+    /// The device is included in the shortcut events caused by the key,
+    /// and in the timed events of the shortcuts it pressed.
+    pub fn handle_device_key(&mut self, device: Option<&Arc<DeviceInfo>>, key: Key, value: i32, time: SystemTime) -> impl Iterator<Item=MatchEvent> + '_ {
+        self.expire(time);
+        let device_changed = device.is_some_and(|device| {
+            // the path is only cloned for the first event of a device
+            match self.device_keys.get_mut(&device.path) {
+                Some(keys) => keys.update(key, value),
+                None => self.device_keys.entry(device.path.clone()).or_default().update(key, value),
+            }
+        });
+        self.current_device = device.cloned();
+        self.current_release = value == 0;
+        // a key stays held as long as any device holds it
+        let held_elsewhere = device.is_some() && self.device_keys.values().any(|keys| keys.keys.contains(key));
+        let changed = match value {
+            1 => self.active_keys.insert(key),
+            0 if !held_elsewhere => self.active_keys.remove(key),
+            _ => false,
+        };
+        if value == 2 {
+            self.repeat_key(key, time);
+        }
+        if (changed || device_changed) && value == 1 {
+            for press in self.pressed.iter_mut().flatten() {
+                press.intervened = true;
+            }
+        }
+        if changed {
+            self.update(key, time);
+            if value == 1 && Modifier::mask_from_key(key) == 0 {
+                self.update_sequence(key, time);
+            }
+            self.update_tap(key, value, time);
+            self.update_chords(key, value, time);
+        } else if device_changed {
+            // the key is also held on another device, which only matters for the shortcuts scoped to this one
+            self.current_scoped_only = true;
+            self.update(key, time);
+            self.current_scoped_only = false;
+        }
+        self.current_device = None;
+        self.current_release = false;
+        self.events.drain(..)
+    }
+
+    /// Synchronize the held keys of a device with a snapshot of its key state
+    ///
+    /// Keys that are no longer held are released and newly held keys are pressed,
+    /// returning the events caused by those synthetic key events.
+    /// Used for keys that were already held when listening started and to recover from dropped events.
+    pub fn sync_device_keys(&mut self, device: Option<&Arc<DeviceInfo>>, keys: &KeySet, time: SystemTime) -> impl Iterator<Item=MatchEvent> + '_ {
+        let held = match device {
+            Some(device) => self.device_keys.get(&device.path).map(|held| held.keys).unwrap_or_default(),
+            None => self.active_keys,
+        };
+        let mut events = Vec::new();
+        for key in held.iter().filter(|key| !keys.contains(*key)) {
+            events.extend(self.handle_device_key(device, key, 0, time));
+        }
+        for key in keys.iter().filter(|key| !held.contains(*key)) {
+            events.extend(self.handle_device_key(device, key, 1, time));
+        }
+        events.into_iter()
+    }
+
+    /// Forget a device that was disconnected or failed, releasing the keys it held
+    ///
+    /// The shortcuts released by this have [DeviceLost](ReleaseReason::DeviceLost) as reason.
+    /// Keys that are also held on another device stay held.
+    pub fn remove_device(&mut self, device: &Arc<DeviceInfo>, time: SystemTime) -> impl Iterator<Item=MatchEvent> + '_ {
+        self.current_reason = ReleaseReason::DeviceLost;
+        let events: Vec<_> = self.sync_device_keys(Some(device), &KeySet::new(), time).collect();
+        self.current_reason = ReleaseReason::KeyReleased;
+        self.device_keys.remove(&device.path);
+        events.into_iter()
+    }
+
+    fn update(&mut self, changed_key: Key, time: SystemTime) {
+        let modifier = Modifier::mask_from_key(changed_key);
+        if self.active_keys.contains(changed_key) {
+            self.modifier_mask |= modifier;
+        } else {
+            self.modifier_mask &= !modifier;
+        }
+        self.update_modifier_only(time);
+        self.update_key(changed_key, time, false);
+        if modifier != 0 || self.shortcuts.is_custom_modifier(changed_key) {
+            // a modifier change can affect any shortcut for a held key
+            let active_keys = self.active_keys;
+            for key in active_keys.iter().filter(|key| *key != changed_key) {
+                self.update_key(key, time, true);
+            }
+        }
+        self.flush_changes();
+    }
+
+    /// Emit the shortcut events of the key event that is being handled
+    ///
+    /// All releases are emitted before the presses, both in the order the shortcuts were registered.
+    /// A shortcut that triggers on release emits both its events as part of the releases.
+    fn flush_changes(&mut self) {
+        self.changes.sort_by_key(|(is_press, position, _)| (*is_press, *position));
+        self.events.extend(self.changes.drain(..).map(|(_, _, event)| event));
+    }
+
+    /// Whether the shortcut at a position is triggered by the held keys, and whether its key is held
+    ///
+    /// Returns `None` for shortcuts scoped to other devices than the one the current key event is from,
+    /// and for unscoped shortcuts while only the scoped shortcuts are updated.
+    fn trigger_state(&self, position: usize) -> Option<(bool, bool)> {
+        let shortcut = &self.shortcuts.shortcuts[position];
+        let options = &self.shortcuts.options[position];
+        let policy = options.policy.as_ref().unwrap_or(&self.policy);
+        let (keys, modifier_mask) = match &options.device {
+            None if self.current_scoped_only => return None,
+            None => (&self.active_keys, self.modifier_mask),
+            Some(selector) => {
+                // scoped shortcuts only change state by keys from the selected devices
+                let device = self.current_device.as_ref().filter(|device| selector.matches(device))?;
+                let keys = &self.device_keys[&device.path];
+                (&keys.keys, keys.modifier_mask)
+            }
+        };
+        let key_held = shortcut.key().is_some_and(|key| keys.contains(key));
+        Some((shortcut.is_triggered_with(keys, modifier_mask, policy), key_held))
+    }
+
+    /// Update the shortcuts of a key, `by_modifier` is whether the update is caused by a modifier change while the key is held
+    fn update_key(&mut self, key: Key, time: SystemTime, by_modifier: bool) {
+        let shortcuts = self.shortcuts.clone();
+        for &position in shortcuts.for_key(Some(key)) {
+            let shortcut = &shortcuts.shortcuts[position];
+            let options = &shortcuts.options[position];
+            let policy = options.policy.as_ref().unwrap_or(&self.policy);
+            let Some((mut is_triggered, key_held)) = self.trigger_state(position) else {
+                continue;
+            };
+            if !key_held {
+                self.interrupted[position] = false;
+            }
+            if is_triggered && self.most_specific_wins {
+                is_triggered = !self.shortcuts.for_key(Some(key)).iter().any(|&other| {
+                    self.shortcuts.shortcuts[other].is_more_specific_than(shortcut)
+                        && matches!(self.trigger_state(other), Some((true, _)))
+                });
+            }
+            let was_triggered = self.pressed[position].is_some();
+            if is_triggered && !was_triggered && by_modifier {
+                // the key was held before the modifiers matched
+                let allowed = if self.interrupted[position] {
+                    policy.rearm
+                } else {
+                    !policy.strict_order
+                };
+                if !allowed {
+                    continue;
+                }
+            }
+            if !is_triggered && was_triggered {
+                self.interrupted[position] = key_held;
+            }
+            if is_triggered && !was_triggered {
+                self.interrupted[position] = false;
+                self.press(position, time);
+            } else if !is_triggered && was_triggered {
+                self.release(position, time, false);
+            }
+        }
+    }
+
+    /// Update the modifier-only shortcuts after a key changed
+    ///
+    /// Modifier-only shortcuts are only pressed by the modifier press that completes them,
+    /// and pressing any other key while they are pressed cancels them.
+    fn update_modifier_only(&mut self, time: SystemTime) {
+        let pressed_key = !self.current_release;
+        let shortcuts = self.shortcuts.clone();
+        for &position in shortcuts.for_key(None) {
+            let Some((mut is_triggered, _)) = self.trigger_state(position) else {
+                continue;
+            };
+            if is_triggered && self.most_specific_wins {
+                let shortcut = &shortcuts.shortcuts[position];
+                is_triggered = !shortcuts.for_key(None).iter().any(|&other| {
+                    shortcuts.shortcuts[other].is_more_specific_than(shortcut)
+                        && matches!(self.trigger_state(other), Some((true, _)))
+                });
+            }
+            let was_triggered = self.pressed[position].is_some();
+            if is_triggered && !was_triggered && pressed_key {
+                self.press(position, time);
+            } else if !is_triggered && was_triggered {
+                self.release(position, time, pressed_key);
+            }
+        }
+    }
+
+    fn press(&mut self, position: usize, time: SystemTime) {
+        let shortcut = &self.shortcuts.shortcuts[position];
+        let options = &self.shortcuts.options[position];
+        let press = Press {
+            time,
+            device: self.current_device.clone(),
+            intervened: false,
+        };
+        if shortcut.trigger != Trigger::Press {
+            trace!(?shortcut, "armed");
+            self.pressed[position] = Some(press);
+            return;
+        }
+        if let Some(hold) = options.hold {
+            self.pending_holds.push((shortcut.clone(), time + hold));
+        }
+        if let Some(Repeat::Rate { delay, interval }) = options.repeat {
+            self.pending_repeats.push((shortcut.clone(), time + delay, interval));
+        }
+        info!(?shortcut, "pressed");
+        let event = self.shortcut_event(position, ShortcutState::Pressed, time, &press);
+        self.changes.push((true, position, event));
+        self.pressed[position] = Some(press);
+    }
+
+    /// Release a pressed shortcut, it's cancelled if `cancel` is set or it's released before its hold threshold
+    ///
+    /// Shortcuts that trigger on release are pressed and released at once, if releasing a key ended them.
+    fn release(&mut self, position: usize, time: SystemTime, cancel: bool) {
+        let Some(press) = self.pressed[position].take() else {
+            return;
+        };
+        let shortcut = &self.shortcuts.shortcuts[position];
+        if shortcut.trigger != Trigger::Press {
+            let alone = shortcut.trigger != Trigger::ReleaseAlone || !press.intervened;
+            let released = self.current_release && self.current_reason == ReleaseReason::KeyReleased;
+            if released && !cancel && alone {
+                info!(?shortcut, "triggered on release");
+                let pressed = self.shortcut_event(position, ShortcutState::Pressed, time, &press);
+                let released = self.shortcut_event(position, ShortcutState::Released, time, &press);
+                self.changes.extend([(false, position, pressed), (false, position, released)]);
+            } else {
+                trace!(?shortcut, "disarmed");
+            }
+            return;
+        }
+        let hold_count = self.pending_holds.len();
+        self.pending_holds.retain(|(pending, _)| pending != shortcut);
+        self.pending_repeats.retain(|(pending, _, _)| pending != shortcut);
+        let state = if cancel || self.pending_holds.len() != hold_count {
+            ShortcutState::Cancelled
+        } else {
+            ShortcutState::Released
+        };
+        info!(?shortcut, "{}", state);
+        let event = self.shortcut_event(position, state, time, &press);
+        self.changes.push((false, position, event));
+    }
+
+    /// Emit repeats for the pressed shortcuts of a key that use the kernel autorepeat
+    ///
+    /// Scoped shortcuts only repeat by the repeats of the selected devices.
+    fn repeat_key(&mut self, key: Key, time: SystemTime) {
+        for &position in self.shortcuts.for_key(Some(key)) {
+            let Some(press) = &self.pressed[position] else {
+                continue;
+            };
+            let options = &self.shortcuts.options[position];
+            let from_device = options.device.as_ref().is_none_or(|selector| {
+                self.current_device.as_ref().is_some_and(|device| selector.matches(device))
+            });
+            if from_device
+                && options.repeat == Some(Repeat::Kernel)
+                && self.shortcuts.shortcuts[position].trigger == Trigger::Press {
+                trace!(shortcut = ?self.shortcuts.shortcuts[position], "repeat");
+                let event = self.shortcut_event(position, ShortcutState::Repeat, time, press);
+                self.events.push(event);
+            }
+        }
+    }
+
+    /// Emit the event for a timed state change of a pressed shortcut
+    fn push_timed_event(&mut self, shortcut: &Shortcut, state: ShortcutState, time: SystemTime) {
+        let Some(position) = self.shortcuts.position(shortcut) else {
+            return;
+        };
+        if let Some(press) = &self.pressed[position] {
+            let event = self.shortcut_event(position, state, time, press);
+            self.events.push(event);
+        }
+    }
+
+    fn shortcut_event(&self, position: usize, state: ShortcutState, time: SystemTime, press: &Press) -> MatchEvent {
+        let held_for = (state != ShortcutState::Pressed).then(|| time.duration_since(press.time).unwrap_or_default());
+        MatchEvent::Shortcut(ShortcutEvent {
+            shortcut: self.shortcuts.shortcuts[position].clone(),
+            state,
+            time,
+            device: self.current_device.clone().or_else(|| press.device.clone()),
+            held_for,
+            reason: matches!(state, ShortcutState::Released | ShortcutState::Cancelled).then_some(self.current_reason),
+        })
+    }
+
+    fn update_sequence(&mut self, key: Key, time: SystemTime) {
+        let depth = self.sequence_progress.len();
+        let mut next = None;
+        for sequence in self.shortcuts.sequences() {
+            let Some(step) = sequence.steps.get(depth) else {
+                continue;
+            };
+            if step.key == Some(key)
+                && sequence.steps.starts_with(&self.sequence_progress)
+                && step.is_triggered_by(&self.active_keys, self.modifier_mask) {
+                if sequence.steps.len() == depth + 1 {
+                    info!(%sequence, "sequence completed");
+                    self.sequence_progress.clear();
+                    self.sequence_deadline = None;
+                    self.events.push(MatchEvent::Sequence(SequenceEvent {
+                        sequence: sequence.clone(),
+                        state: SequenceState::Completed,
+                    }));
+                    return;
+                }
+                next.get_or_insert_with(|| step.clone());
+            }
+        }
+
+        match next {
+            Some(step) => {
+                self.sequence_progress.push(step);
+                self.sequence_deadline = Some(time + self.sequence_timeout);
+                self.events.push(MatchEvent::Sequence(SequenceEvent {
+                    sequence: KeySequence::new(self.sequence_progress.clone()),
+                    state: SequenceState::InProgress,
+                }));
+            }
+            None if depth > 0 => {
+                // the key that aborted the sequence can still start a new one
+                self.abort_sequence();
+                self.update_sequence(key, time);
+            }
+            None => self.abort_sequence(),
+        }
+    }
+
+    fn update_tap(&mut self, key: Key, value: i32, time: SystemTime) {
+        if value == 1 {
+            if self.tap_streak.as_ref().is_some_and(|streak| streak.key != key) {
+                self.finish_tap_streak();
+            }
+            // any other key being held or pressed prevents a tap
+            self.tap_candidate = (self.active_keys.len() == 1).then_some((key, time));
+            return;
+        }
+        let tapped = self.tap_candidate.take().is_some_and(|(tap_key, pressed)| {
+            tap_key == key && time.duration_since(pressed).unwrap_or_default() <= self.tap_timeout
+        });
+        if !tapped {
+            self.finish_tap_streak();
+            return;
+        }
+
+        let count = match &self.tap_streak {
+            Some(streak) if streak.key == key => streak.count.saturating_add(1),
+            _ => 1,
+        };
+        self.tap_streak = Some(TapStreak {
+            key,
+            count,
+            deadline: time + self.multi_tap_window,
+        });
+        // wait for the multi-tap window to close if the taps could still continue into a binding with a higher count
+        self.prune_tap_streak();
+    }
+
+    /// Finish the tap streak if it can't continue into a registered tap with a higher count
+    fn prune_tap_streak(&mut self) {
+        let can_continue = self.tap_streak.as_ref().is_some_and(|streak| {
+            self.shortcuts.taps().any(|tap| tap.key.matches(streak.key) && tap.count > streak.count)
+        });
+        if !can_continue {
+            self.finish_tap_streak();
+        }
+    }
+
+    /// Trigger the tap bindings with the highest count that doesn't exceed the count of the streak
+    fn finish_tap_streak(&mut self) {
+        let Some(streak) = self.tap_streak.take() else {
+            return;
+        };
+        let matching = || self.shortcuts
+            .taps()
+            .filter(|tap| tap.key.matches(streak.key) && tap.count <= streak.count);
+        let Some(best) = matching().map(|tap| tap.count).max() else {
+            return;
+        };
+        for tap in matching().filter(|tap| tap.count == best) {
+            info!(%tap, count = streak.count, "tapped");
+            self.events.push(MatchEvent::Tap(TapEvent {
+                tap: tap.clone(),
+                count: streak.count,
+            }));
+        }
+    }
+
+    fn release_chords(&mut self, chords: Vec<Chord>) {
+        for chord in chords {
+            info!(%chord, "chord released");
+            self.events.push(MatchEvent::Chord(ChordEvent {
+                chord,
+                state: ShortcutState::Released,
+            }));
+        }
+    }
+
+    fn update_chords(&mut self, key: Key, value: i32, time: SystemTime) {
+        if value == 0 {
+            self.chord_presses.retain(|(held, _)| *held != key);
+            let (released, active) = std::mem::take(&mut self.active_chords)
+                .into_iter()
+                .partition(|chord| chord.contains(key));
+            self.active_chords = active;
+            self.release_chords(released);
+            return;
+        }
+        if !self.shortcuts.chords().any(|chord| chord.contains(key)) {
+            return;
+        }
+        self.chord_presses.push((key, time));
+        if self.modifier_mask != 0 {
+            return;
+        }
+        let pressed: Vec<_> = self.shortcuts
+            .chords()
+            .filter(|chord| chord.contains(key) && !self.active_chords.contains(chord))
+            .filter(|chord| {
+                let first = chord.keys().iter().map(|chord_key| {
+                    self.chord_presses
+                        .iter()
+                        .find(|(held, _)| held == chord_key)
+                        .map(|(_, pressed)| *pressed)
+                }).collect::<Option<Vec<_>>>().and_then(|presses| presses.into_iter().min());
+                first.is_some_and(|first| time.duration_since(first).unwrap_or_default() <= self.chord_window)
+            })
+            .cloned()
+            .collect();
+        for chord in pressed {
+            info!(%chord, "chord pressed");
+            self.active_chords.push(chord.clone());
+            self.events.push(MatchEvent::Chord(ChordEvent {
+                chord,
+                state: ShortcutState::Pressed,
+            }));
+        }
+    }
+
+    /// Handle the sequence, multi-tap and hold deadlines that have passed
+    fn expire(&mut self, now: SystemTime) {
+        self.pending_holds.sort_by_key(|(_, deadline)| *deadline);
+        let held = self.pending_holds.partition_point(|(_, deadline)| *deadline <= now);
+        let held: Vec<_> = self.pending_holds.drain(..held).collect();
+        for (shortcut, deadline) in held {
+            info!(?shortcut, "held");
+            self.push_timed_event(&shortcut, ShortcutState::Held, deadline);
+        }
+        let mut repeats = Vec::new();
+        for (shortcut, next, interval) in &mut self.pending_repeats {
+            if *next <= now {
+                repeats.push((shortcut.clone(), *next));
+                // repeats that were missed while not being ticked are skipped
+                *next = (*next + *interval).max(now + *interval);
+            }
+        }
+        for (shortcut, time) in repeats {
+            trace!(?shortcut, "repeat");
+            self.push_timed_event(&shortcut, ShortcutState::Repeat, time);
+        }
+        if self.sequence_deadline.is_some_and(|deadline| deadline <= now) {
+            self.abort_sequence();
+        }
+        if self.tap_streak.as_ref().is_some_and(|streak| streak.deadline <= now) {
+            self.finish_tap_streak();
+        }
+    }
+
+    /// Abort the sequence that is being typed if no registered sequence continues it anymore
+    fn prune_sequence(&mut self) {
+        let depth = self.sequence_progress.len();
+        let continues = self.shortcuts.sequences().any(|sequence| {
+            sequence.steps.len() > depth && sequence.steps.starts_with(&self.sequence_progress)
+        });
+        if !continues {
+            self.abort_sequence();
+        }
+    }
+
+    fn abort_sequence(&mut self) {
+        self.sequence_deadline = None;
+        if !self.sequence_progress.is_empty() {
+            let sequence = KeySequence::new(std::mem::take(&mut self.sequence_progress));
+            info!(%sequence, "sequence aborted");
+            self.events.push(MatchEvent::Sequence(SequenceEvent {
+                sequence,
+                state: SequenceState::Aborted,
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MatchEvent, ShortcutMatcher, ShortcutSet};
+    use crate::{Chord, DeviceInfo, DeviceSelector, Key, KeySequence, KeySet, MatchPolicy, Modifier, ModifierList, ReleaseReason, Repeat, SequenceConflict, SequenceState, Shortcut, ShortcutConflict, ShortcutEvent, ShortcutOptions, ShortcutState, Tap};
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+    use test_case::test_case;
+
+    /// The shortcut events among the match events, mapped by `map`
+    fn shortcut_events<T>(events: impl IntoIterator<Item=MatchEvent>, map: impl Fn(ShortcutEvent) -> Option<T>) -> Vec<T> {
+        events
+            .into_iter()
+            .filter_map(|event| match event {
+                MatchEvent::Shortcut(event) => map(event),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Feed keys as (key, value) to the matcher, returning the shortcut events mapped by `map`
+    fn feed<T>(matcher: &mut ShortcutMatcher, keys: &[(Key, i32)], map: impl Fn(ShortcutEvent) -> Option<T>) -> Vec<T> {
+        let events: Vec<_> = keys
+            .iter()
+            .flat_map(|(key, value)| matcher.handle_key(*key, *value, SystemTime::UNIX_EPOCH).collect::<Vec<_>>())
+            .collect();
+        shortcut_events(events, map)
+    }
+
+    /// Feed keys as (key, value) to a matcher with the shortcuts, returning the shortcut events mapped by `map`
+    fn run<T>(shortcuts: &[&str], keys: &[(Key, i32)], map: impl Fn(ShortcutEvent) -> Option<T>) -> Vec<T> {
+        let mut matcher = ShortcutMatcher::new();
+        for shortcut in shortcuts {
+            matcher.add(shortcut.parse().unwrap());
+        }
+        feed(&mut matcher, keys, map)
+    }
+
+    fn state(event: ShortcutEvent) -> Option<ShortcutState> {
+        Some(event.state)
+    }
+
+    fn named_state(event: ShortcutEvent) -> Option<(String, ShortcutState)> {
+        Some((event.shortcut.to_string(), event.state))
+    }
+
+    fn named_reason(event: ShortcutEvent) -> Option<(String, ShortcutState, Option<ReleaseReason>)> {
+        Some((event.shortcut.to_string(), event.state, event.reason))
+    }
+
+    /// Press and release keys, one second apart
+    fn run_sequences(sequences: &[&str], keys: &[Key]) -> Vec<(String, SequenceState)> {
+        let mut matcher = ShortcutMatcher::new();
+        matcher.set_sequence_timeout(Duration::from_millis(1500));
+        for sequence in sequences {
+            matcher.add_sequence(sequence.parse().unwrap()).unwrap();
+        }
+        let mut events = Vec::new();
+        let mut time = SystemTime::UNIX_EPOCH;
+        for key in keys {
+            time += Duration::from_secs(1);
+            events.extend(matcher.tick(time).collect::<Vec<_>>());
+            events.extend(matcher.handle_key(*key, 1, time).collect::<Vec<_>>());
+            events.extend(matcher.handle_key(*key, 0, time).collect::<Vec<_>>());
+        }
+        events.extend(matcher.tick(time + Duration::from_secs(10)).collect::<Vec<_>>());
+        events.into_iter().filter_map(|event| match event {
+            MatchEvent::Sequence(event) => Some((event.sequence.to_string(), event.state)),
+            _ => None,
+        }).collect()
+    }
+
+    #[test_case(& [(Key::KeyLeftCtrl, 1), (Key::KeyP, 1), (Key::KeyP, 0), (Key::KeyLeftCtrl, 0)] => vec![
+        ("<Ctrl>-KeyP".to_string(), ShortcutState::Pressed),
+        ("<Ctrl>-KeyP".to_string(), ShortcutState::Released),
+    ]; "press and release")]
+    #[test_case(& [(Key::KeyLeftCtrl, 1), (Key::KeyP, 1), (Key::KeyLeftCtrl, 0), (Key::KeyP, 0)] => vec![
+        ("<Ctrl>-KeyP".to_string(), ShortcutState::Pressed),
+        ("<Ctrl>-KeyP".to_string(), ShortcutState::Released),
+    ]; "release modifier first")]
+    #[test_case(& [(Key::KeyLeftCtrl, 1), (Key::KeyP, 1), (Key::KeyP, 2), (Key::KeyP, 2)] => vec![
+        ("<Ctrl>-KeyP".to_string(), ShortcutState::Pressed),
+    ]; "repeat is ignored")]
+    #[test_case(& [(Key::KeyLeftCtrl, 1), (Key::KeyLeftAlt, 1), (Key::KeyP, 1)] => vec![]; "extra modifier")]
+    #[test_case(& [(Key::KeyLeftCtrl, 1), (Key::KeyP, 1), (Key::KeyLeftAlt, 1), (Key::KeyLeftAlt, 0)] => vec![
+        ("<Ctrl>-KeyP".to_string(), ShortcutState::Pressed),
+        ("<Ctrl>-KeyP".to_string(), ShortcutState::Released),
+        ("<Ctrl>-KeyP".to_string(), ShortcutState::Pressed),
+    ]; "extra modifier while pressed")]
+    #[test_case(& [(Key::KeyP, 0), (Key::KeyLeftCtrl, 0)] => vec![]; "release without press")]
+    fn matcher_test(keys: &[(Key, i32)]) -> Vec<(String, ShortcutState)> {
+        run(&["<Ctrl>-KeyP"], keys, named_state)
+    }
+
+    #[test]
+    fn remove_shortcut() {
+        let shortcut: Shortcut = "<Ctrl>-KeyP".parse().unwrap();
+        let mut matcher = ShortcutMatcher::new();
+        assert!(matcher.add(shortcut.clone()));
+        assert!(!matcher.add(shortcut.clone()));
+        assert!(matcher.remove(&shortcut));
+        assert!(!matcher.has(&shortcut));
+        assert_eq!(0, matcher.handle_key(Key::KeyLeftCtrl, 1, SystemTime::now()).count());
+        assert_eq!(0, matcher.handle_key(Key::KeyP, 1, SystemTime::now()).count());
+    }
+
+    #[test]
+    fn multiple_shortcuts() {
+        let events = run(
+            &["KeyA", "<Shift>-KeyA"],
+            &[(Key::KeyA, 1), (Key::KeyLeftShift, 1), (Key::KeyA, 0)],
+            named_state,
+        );
+        assert_eq!(vec![
+            ("KeyA".to_string(), ShortcutState::Pressed),
+            ("KeyA".to_string(), ShortcutState::Released),
+            ("<Shift>-KeyA".to_string(), ShortcutState::Pressed),
+            ("<Shift>-KeyA".to_string(), ShortcutState::Released),
+        ], events);
+    }
+
+    #[test]
+    fn set_keeps_order_after_remove() {
+        let mut set: ShortcutSet = ["KeyA", "<Ctrl>-KeyA", "KeyB"]
+            .iter()
+            .map(|shortcut| shortcut.parse().unwrap())
+            .collect();
+        assert!(set.remove(&"KeyA".parse().unwrap()));
+        assert!(!set.remove(&"KeyA".parse().unwrap()));
+        let shortcuts: Vec<_> = set.iter().map(|shortcut| shortcut.to_string()).collect();
+        assert_eq!(vec!["<Ctrl>-KeyA", "KeyB"], shortcuts);
+        assert_eq!(Some(1), set.position(&"KeyB".parse().unwrap()));
+    }
+
+    #[test]
+    fn set_shortcuts_keeps_pressed_state() {
+        let mut matcher = ShortcutMatcher::new();
+        matcher.add("KeyA".parse().unwrap());
+        matcher.add("KeyB".parse().unwrap());
+        assert_eq!(1, matcher.handle_key(Key::KeyB, 1, SystemTime::now()).count());
+
+        let set: ShortcutSet = ["KeyB", "KeyC"].iter().map(|shortcut| shortcut.parse().unwrap()).collect();
+        assert_eq!(0, matcher.set_shortcuts(Arc::new(set)).count());
+
+        let events: Vec<_> = matcher.handle_key(Key::KeyB, 0, SystemTime::now()).collect();
+        assert_eq!(1, events.len());
+        assert!(matches!(&events[0], MatchEvent::Shortcut(event) if event.state == ShortcutState::Released));
+    }
+
+    #[test_case(& [Key::KeyA, Key::KeyB] => vec![
+        ("KeyA".to_string(), SequenceState::InProgress),
+        ("KeyA KeyB".to_string(), SequenceState::Completed),
+    ]; "complete")]
+    #[test_case(& [Key::KeyA, Key::KeyC, Key::KeyD] => vec![
+        ("KeyA".to_string(), SequenceState::InProgress),
+        ("KeyA KeyC".to_string(), SequenceState::InProgress),
+        ("KeyA KeyC KeyD".to_string(), SequenceState::Completed),
+    ]; "shared prefix")]
+    #[test_case(& [Key::KeyA, Key::KeyX, Key::KeyB] => vec![
+        ("KeyA".to_string(), SequenceState::InProgress),
+        ("KeyA".to_string(), SequenceState::Aborted),
+    ]; "unknown key aborts")]
+    #[test_case(& [Key::KeyA] => vec![
+        ("KeyA".to_string(), SequenceState::InProgress),
+        ("KeyA".to_string(), SequenceState::Aborted),
+    ]; "timeout")]
+    #[test_case(& [Key::KeyA, Key::KeyA, Key::KeyB] => vec![
+        ("KeyA".to_string(), SequenceState::InProgress),
+        ("KeyA".to_string(), SequenceState::Aborted),
+        ("KeyA".to_string(), SequenceState::InProgress),
+        ("KeyA KeyB".to_string(), SequenceState::Completed),
+    ]; "aborting key starts a new sequence")]
+    #[test_case(& [Key::KeyX, Key::KeyY] => vec![]; "unrelated keys")]
+    fn sequence_test(keys: &[Key]) -> Vec<(String, SequenceState)> {
+        run_sequences(&["KeyA KeyB", "KeyA KeyC KeyD"], keys)
+    }
+
+    #[test]
+    fn sequence_with_modifiers() {
+        let mut matcher = ShortcutMatcher::new();
+        matcher.add_sequence("<Ctrl>-KeyX <Ctrl>-KeyS".parse().unwrap()).unwrap();
+        let time = SystemTime::UNIX_EPOCH;
+        let keys = [
+            (Key::KeyLeftCtrl, 1),
+            (Key::KeyX, 1),
+            (Key::KeyX, 0),
+            (Key::KeyS, 1),
+        ];
+        let states: Vec<_> = keys.iter()
+            .flat_map(|(key, value)| matcher.handle_key(*key, *value, time).collect::<Vec<_>>())
+            .filter_map(|event| match event {
+                MatchEvent::Sequence(event) => Some(event.state),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec![SequenceState::InProgress, SequenceState::Completed], states);
+    }
+
+    #[test]
+    fn sequence_conflicts() {
+        let mut set = ShortcutSet::new();
+        set.insert("<Ctrl>-KeyS".parse().unwrap());
+        assert!(set.insert_sequence("<Ctrl>-KeyX KeyA".parse().unwrap()).unwrap());
+        assert!(!set.insert_sequence("<Ctrl>-KeyX KeyA".parse().unwrap()).unwrap());
+        assert!(matches!(
+            set.insert_sequence("<LeftCtrl>-KeyS KeyA".parse().unwrap()),
+            Err(SequenceConflict::Shortcut { .. })
+        ));
+        assert!(matches!(
+            set.insert_sequence("<Ctrl>-KeyX KeyA KeyB".parse().unwrap()),
+            Err(SequenceConflict::Sequence { .. })
+        ));
+        let sequence: KeySequence = "<Ctrl>-KeyX KeyA".parse().unwrap();
+        assert_eq!(Some(&sequence), set.overlapping_sequence(&"<LeftCtrl>-KeyX".parse().unwrap()));
+        assert!(!set.insert("<LeftCtrl>-KeyX".parse().unwrap()));
+        assert!(matches!(
+            set.insert_checked("<Ctrl>-KeyX".parse().unwrap(), false),
+            Err(ShortcutConflict::Sequence { .. })
+        ));
+        assert!(!set.contains(&"<LeftCtrl>-KeyX".parse().unwrap()));
+        assert!(set.remove_sequence(&sequence));
+        assert!(!set.contains_sequence(&sequence));
+    }
+
+    #[test]
+    fn remove_sequence_aborts_progress() {
+        let mut matcher = ShortcutMatcher::new();
+        let sequence: KeySequence = "KeyA KeyB".parse().unwrap();
+        matcher.add_sequence(sequence.clone()).unwrap();
+        matcher.add_sequence("KeyA KeyC".parse().unwrap()).unwrap();
+        matcher.handle_key(Key::KeyA, 1, SystemTime::UNIX_EPOCH).for_each(drop);
+
+        // the other sequence still continues the typed strokes
+        assert!(matcher.remove_sequence(&sequence));
+        assert_eq!(0, matcher.tick(SystemTime::UNIX_EPOCH).count());
+        assert!(matcher.remove_sequence(&"KeyA KeyC".parse().unwrap()));
+        let events: Vec<_> = matcher.tick(SystemTime::UNIX_EPOCH).collect();
+        assert!(matches!(&events[..], [MatchEvent::Sequence(event)] if event.state == SequenceState::Aborted));
+        assert_eq!(None, matcher.deadline());
+    }
+
+    /// Feed keys as (key, value, milliseconds since the start), returning the taps with their count
+    fn run_taps(taps: &[&str], keys: &[(Key, i32, u64)]) -> Vec<(String, u8)> {
+        let mut matcher = ShortcutMatcher::new();
+        matcher.add("<Meta>-KeyN".parse().unwrap());
+        for tap in taps {
+            matcher.add_tap(tap.parse().unwrap());
+        }
+        let mut events = Vec::new();
+        let mut time = SystemTime::UNIX_EPOCH;
+        for (key, value, millis) in keys {
+            time = SystemTime::UNIX_EPOCH + Duration::from_millis(*millis);
+            events.extend(matcher.handle_key(*key, *value, time).collect::<Vec<_>>());
+        }
+        events.extend(matcher.tick(time + Duration::from_secs(10)).collect::<Vec<_>>());
+        events.into_iter().filter_map(|event| match event {
+            MatchEvent::Tap(event) => Some((event.tap.to_string(), event.count)),
+            _ => None,
+        }).collect()
+    }
+
+    fn tap_names(taps: Vec<(String, u8)>) -> Vec<String> {
+        taps.into_iter().map(|(tap, _)| tap).collect()
+    }
+
+    #[test_case(& [(Key::KeyLeftMeta, 1, 0), (Key::KeyLeftMeta, 0, 100)] => vec!["tap:<Meta>", "tap:<LeftMeta>"]; "tap modifier")]
+    #[test_case(& [(Key::KeyRightMeta, 1, 0), (Key::KeyRightMeta, 0, 100)] => vec!["tap:<Meta>"]; "other side")]
+    #[test_case(& [(Key::KeyEsc, 1, 0), (Key::KeyEsc, 2, 50), (Key::KeyEsc, 0, 100)] => vec!["tap:KeyEsc"]; "tap key")]
+    #[test_case(& [(Key::KeyLeftMeta, 1, 0), (Key::KeyLeftMeta, 0, 500)] => Vec::<String>::new(); "held too long")]
+    #[test_case(& [(Key::KeyLeftMeta, 1, 0), (Key::KeyN, 1, 10), (Key::KeyN, 0, 20), (Key::KeyLeftMeta, 0, 30)] => Vec::<String>::new(); "used as modifier")]
+    #[test_case(& [(Key::KeyLeftCtrl, 1, 0), (Key::KeyLeftMeta, 1, 10), (Key::KeyLeftMeta, 0, 20)] => Vec::<String>::new(); "other key held")]
+    #[test_case(& [(Key::KeyLeftMeta, 1, 0), (Key::KeyEsc, 1, 10), (Key::KeyLeftMeta, 0, 20), (Key::KeyEsc, 0, 30)] => Vec::<String>::new(); "overlapping taps")]
+    fn tap_test(keys: &[(Key, i32, u64)]) -> Vec<String> {
+        tap_names(run_taps(&["tap:<Meta>", "tap:<LeftMeta>", "tap:KeyEsc"], keys))
+    }
+
+    fn taps(key: Key, times: &[u64]) -> Vec<(Key, i32, u64)> {
+        times.iter().flat_map(|time| [(key, 1, *time), (key, 0, *time + 50)]).collect()
+    }
+
+    #[test_case(& taps(Key::KeyEsc, & [0]) => vec![("tap:KeyEsc".to_string(), 1)]; "single")]
+    #[test_case(& taps(Key::KeyEsc, & [0, 100]) => vec![("tap:KeyEsc*2".to_string(), 2)]; "double")]
+    #[test_case(& taps(Key::KeyEsc, & [0, 100, 200]) => vec![("tap:KeyEsc*3".to_string(), 3)]; "triple")]
+    #[test_case(& taps(Key::KeyEsc, & [0, 100, 200, 300]) => vec![("tap:KeyEsc*3".to_string(), 3), ("tap:KeyEsc".to_string(), 1)]; "highest count triggers immediately")]
+    #[test_case(& taps(Key::KeyEsc, & [0, 1000]) => vec![("tap:KeyEsc".to_string(), 1), ("tap:KeyEsc".to_string(), 1)]; "window closed")]
+    fn multi_tap_test(keys: &[(Key, i32, u64)]) -> Vec<(String, u8)> {
+        run_taps(&["tap:KeyEsc", "tap:KeyEsc*2", "tap:KeyEsc*3"], keys)
+    }
+
+    #[test]
+    fn multi_tap_between_bindings() {
+        assert_eq!(vec![
+            ("tap:KeyEsc".to_string(), 2),
+        ], run_taps(&["tap:KeyEsc", "tap:KeyEsc*3"], &taps(Key::KeyEsc, &[0, 100])));
+    }
+
+    #[test]
+    fn multi_tap_interrupted() {
+        let mut keys = taps(Key::KeyLeftShift, &[0, 100]);
+        keys.push((Key::KeyA, 1, 200));
+        keys.extend(taps(Key::KeyLeftShift, &[250]));
+        assert_eq!(vec![
+            ("tap:<LeftShift>*2".to_string(), 2),
+        ], run_taps(&["tap:<LeftShift>*2"], &keys));
+    }
+
+    #[test]
+    fn single_tap_waits_for_window() {
+        let mut matcher = ShortcutMatcher::new();
+        matcher.add_tap("tap:KeyEsc".parse().unwrap());
+        matcher.add_tap("tap:KeyEsc*2".parse().unwrap());
+        let start = SystemTime::UNIX_EPOCH;
+        assert_eq!(0, matcher.handle_key(Key::KeyEsc, 1, start).count());
+        assert_eq!(0, matcher.handle_key(Key::KeyEsc, 0, start + Duration::from_millis(50)).count());
+        let deadline = matcher.deadline().unwrap();
+        assert_eq!(start + Duration::from_millis(300), deadline);
+        assert_eq!(0, matcher.tick(deadline - Duration::from_millis(1)).count());
+        assert_eq!(1, matcher.tick(deadline).count());
+        assert_eq!(None, matcher.deadline());
+    }
+
+    #[test]
+    fn tap_does_not_block_shortcut() {
+        let mut matcher = ShortcutMatcher::new();
+        matcher.add("<Meta>-KeyN".parse().unwrap());
+        let tap: Tap = "tap:<Meta>".parse().unwrap();
+        assert!(matcher.add_tap(tap.clone()));
+        assert!(!matcher.add_tap(tap));
+        let time = SystemTime::UNIX_EPOCH;
+        assert_eq!(0, matcher.handle_key(Key::KeyLeftMeta, 1, time).count());
+        let events: Vec<_> = matcher.handle_key(Key::KeyN, 1, time).collect();
+        assert!(matches!(&events[..], [MatchEvent::Shortcut(event)] if event.state == ShortcutState::Pressed));
+    }
+
+    /// Feed keys as (key, value, milliseconds since the start) to a matcher with `<Ctrl>-KeyQ` held for 800ms
+    fn run_hold(keys: &[(Key, i32, u64)], end: u64) -> Vec<ShortcutState> {
+        let mut matcher = ShortcutMatcher::new();
+        matcher.add_with("<Ctrl>-KeyQ".parse().unwrap(), ShortcutOptions {
+            hold: Some(Duration::from_millis(800)),
+            ..ShortcutOptions::default()
+        });
+        let at = |millis: u64| SystemTime::UNIX_EPOCH + Duration::from_millis(millis);
+        let mut events = Vec::new();
+        for (key, value, millis) in keys {
+            events.extend(matcher.tick(at(*millis)).collect::<Vec<_>>());
+            events.extend(matcher.handle_key(*key, *value, at(*millis)).collect::<Vec<_>>());
+        }
+        events.extend(matcher.tick(at(end)).collect::<Vec<_>>());
+        events.into_iter().filter_map(|event| match event {
+            MatchEvent::Shortcut(event) => Some(event.state),
+            _ => None,
+        }).collect()
+    }
+
+    #[test_case(& [(Key::KeyLeftCtrl, 1, 0), (Key::KeyQ, 1, 100), (Key::KeyQ, 0, 1000)], 2000 => vec![
+        ShortcutState::Pressed, ShortcutState::Held, ShortcutState::Released,
+    ]; "held")]
+    #[test_case(& [(Key::KeyLeftCtrl, 1, 0), (Key::KeyQ, 1, 100), (Key::KeyQ, 0, 500)], 2000 => vec![
+        ShortcutState::Pressed, ShortcutState::Cancelled,
+    ]; "released early")]
+    #[test_case(& [(Key::KeyLeftCtrl, 1, 0), (Key::KeyQ, 1, 100)], 850 => vec![
+        ShortcutState::Pressed,
+    ]; "not held yet")]
+    #[test_case(& [(Key::KeyLeftCtrl, 1, 0), (Key::KeyQ, 1, 100)], 900 => vec![
+        ShortcutState::Pressed, ShortcutState::Held,
+    ]; "held by tick")]
+    fn hold_test(keys: &[(Key, i32, u64)], end: u64) -> Vec<ShortcutState> {
+        run_hold(keys, end)
+    }
+
+    #[test]
+    fn hold_deadline() {
+        let mut matcher = ShortcutMatcher::new();
+        let shortcut: Shortcut = "KeyQ".parse().unwrap();
+        matcher.add_with(shortcut.clone(), ShortcutOptions {
+            hold: Some(Duration::from_millis(800)),
+            ..ShortcutOptions::default()
+        });
+        assert_eq!(Some(Duration::from_millis(800)), matcher.shortcuts().options(&shortcut).unwrap().hold);
+        assert_eq!(None, matcher.deadline());
+        assert_eq!(1, matcher.handle_key(Key::KeyQ, 1, SystemTime::UNIX_EPOCH).count());
+        assert_eq!(Some(SystemTime::UNIX_EPOCH + Duration::from_millis(800)), matcher.deadline());
+        assert!(!matcher.add_with(shortcut.clone(), ShortcutOptions::default()));
+        assert_eq!(None, matcher.shortcuts().options(&shortcut).unwrap().hold);
+    }
+
+    /// Feed keys as (key, value, milliseconds since the start), ticking every 100ms
+    fn run_repeat(repeat: Repeat, keys: &[(Key, i32, u64)]) -> Vec<(ShortcutState, u64)> {
+        let mut matcher = ShortcutMatcher::new();
+        matcher.add_with("<Ctrl>-KeyUp".parse().unwrap(), ShortcutOptions {
+            repeat: Some(repeat),
+            ..ShortcutOptions::default()
+        });
+        matcher.add("<Ctrl>-KeyDown".parse().unwrap());
+        let at = |millis: u64| SystemTime::UNIX_EPOCH + Duration::from_millis(millis);
+        let mut events = Vec::new();
+        let mut keys = keys.iter().peekable();
+        for millis in (0..=1000).step_by(100) {
+            events.extend(matcher.tick(at(millis)).map(|event| (event, millis)).collect::<Vec<_>>());
+            while let Some((key, value, _)) = keys.next_if(|(_, _, key_millis)| *key_millis == millis) {
+                events.extend(matcher.handle_key(*key, *value, at(millis)).map(|event| (event, millis)).collect::<Vec<_>>());
+            }
+        }
+        events.into_iter().filter_map(|(event, millis)| match event {
+            MatchEvent::Shortcut(event) => Some((event.state, millis)),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn kernel_repeat() {
+        let events = run_repeat(Repeat::Kernel, &[
+            (Key::KeyLeftCtrl, 1, 0),
+            (Key::KeyUp, 1, 100),
+            (Key::KeyUp, 2, 300),
+            (Key::KeyUp, 2, 400),
+            (Key::KeyUp, 0, 500),
+            (Key::KeyDown, 1, 600),
+            (Key::KeyDown, 2, 700),
+        ]);
+        assert_eq!(vec![
+            (ShortcutState::Pressed, 100),
+            (ShortcutState::Repeat, 300),
+            (ShortcutState::Repeat, 400),
+            (ShortcutState::Released, 500),
+            (ShortcutState::Pressed, 600),
+        ], events);
+    }
+
+    #[test]
+    fn generated_repeat() {
+        let repeat = Repeat::Rate {
+            delay: Duration::from_millis(300),
+            interval: Duration::from_millis(200),
+        };
+        let events = run_repeat(repeat, &[
+            (Key::KeyLeftCtrl, 1, 0),
+            (Key::KeyUp, 1, 100),
+            (Key::KeyUp, 2, 300),
+            (Key::KeyUp, 0, 900),
+        ]);
+        assert_eq!(vec![
+            (ShortcutState::Pressed, 100),
+            (ShortcutState::Repeat, 400),
+            (ShortcutState::Repeat, 600),
+            (ShortcutState::Repeat, 800),
+            (ShortcutState::Released, 900),
+        ], events);
+    }
+
+    #[test]
+    fn held_for() {
+        let mut matcher = ShortcutMatcher::new();
+        matcher.add("KeyA".parse().unwrap());
+        let device = Arc::new(DeviceInfo::from(std::path::Path::new("/dev/input/event0")));
+        let start = SystemTime::UNIX_EPOCH;
+        let pressed: Vec<_> = matcher.handle_device_key(Some(&device), Key::KeyA, 1, start).collect();
+        let released: Vec<_> = matcher.handle_key(Key::KeyA, 0, start + Duration::from_millis(300)).collect();
+        match (&pressed[..], &released[..]) {
+            ([MatchEvent::Shortcut(pressed)], [MatchEvent::Shortcut(released)]) => {
+                assert_eq!(start, pressed.time);
+                assert_eq!(None, pressed.held_for);
+                assert_eq!(Some(Duration::from_millis(300)), released.held_for);
+                // without a device for the release, the device that pressed the shortcut is used
+                assert_eq!(Some(&device), released.device.as_ref());
+            }
+            events => panic!("unexpected events {:?}", events),
+        }
+    }
+
+    #[test]
+    fn device_scoped_shortcut() {
+        let keyboard = Arc::new(DeviceInfo::from(std::path::Path::new("/dev/input/event0")));
+        let pad = Arc::new(DeviceInfo {
+            name: Some("Macro Pad".into()),
+            ..DeviceInfo::from(std::path::Path::new("/dev/input/event1"))
+        });
+        let mut matcher = ShortcutMatcher::new();
+        matcher.add_with("KeyF13".parse().unwrap(), ShortcutOptions {
+            device: Some(DeviceSelector::Name("Macro Pad".into())),
+            ..ShortcutOptions::default()
+        });
+        let time = SystemTime::UNIX_EPOCH;
+        let mut states = |device: &Arc<DeviceInfo>, key: Key, value: i32| -> Vec<ShortcutState> {
+            matcher.handle_device_key(Some(device), key, value, time).filter_map(|event| match event {
+                MatchEvent::Shortcut(event) => Some(event.state),
+                _ => None,
+            }).collect()
+        };
+
+        assert!(states(&keyboard, Key::KeyF13, 1).is_empty());
+        assert!(states(&keyboard, Key::KeyF13, 0).is_empty());
+        // modifiers held on other devices don't affect scoped shortcuts
+        assert!(states(&keyboard, Key::KeyLeftCtrl, 1).is_empty());
+        assert_eq!(vec![ShortcutState::Pressed], states(&pad, Key::KeyF13, 1));
+        assert!(states(&keyboard, Key::KeyLeftCtrl, 0).is_empty());
+        assert_eq!(vec![ShortcutState::Released], states(&pad, Key::KeyF13, 0));
+    }
+
+    fn scoped_matcher() -> (ShortcutMatcher, Arc<DeviceInfo>, Arc<DeviceInfo>) {
+        let keyboard = Arc::new(DeviceInfo::from(std::path::Path::new("/dev/input/event0")));
+        let pad = Arc::new(DeviceInfo::from(std::path::Path::new("/dev/input/event1")));
+        let mut matcher = ShortcutMatcher::new();
+        matcher.add_with("KeyF13".parse().unwrap(), ShortcutOptions {
+            device: Some(DeviceSelector::Path(pad.path.clone())),
+            repeat: Some(Repeat::Kernel),
+            ..ShortcutOptions::default()
+        });
+        (matcher, keyboard, pad)
+    }
+
+    fn device_states(matcher: &mut ShortcutMatcher, device: &Arc<DeviceInfo>, key: Key, value: i32) -> Vec<ShortcutState> {
+        shortcut_events(matcher.handle_device_key(Some(device), key, value, SystemTime::UNIX_EPOCH), state)
+    }
+
+    #[test]
+    fn scoped_key_held_on_two_devices() {
+        let (mut matcher, keyboard, pad) = scoped_matcher();
+        assert!(device_states(&mut matcher, &keyboard, Key::KeyF13, 1).is_empty());
+        assert_eq!(vec![ShortcutState::Pressed], device_states(&mut matcher, &pad, Key::KeyF13, 1));
+        // repeats of other devices don't drive the scoped shortcut
+        assert!(device_states(&mut matcher, &keyboard, Key::KeyF13, 2).is_empty());
+        assert_eq!(vec![ShortcutState::Repeat], device_states(&mut matcher, &pad, Key::KeyF13, 2));
+        assert_eq!(vec![ShortcutState::Released], device_states(&mut matcher, &pad, Key::KeyF13, 0));
+        assert!(device_states(&mut matcher, &keyboard, Key::KeyF13, 0).is_empty());
+    }
+
+    #[test]
+    fn scoped_device_removed_while_key_held_elsewhere() {
+        let (mut matcher, keyboard, pad) = scoped_matcher();
+        assert_eq!(vec![ShortcutState::Pressed], device_states(&mut matcher, &pad, Key::KeyF13, 1));
+        assert!(device_states(&mut matcher, &keyboard, Key::KeyF13, 1).is_empty());
+        let events = shortcut_events(matcher.remove_device(&pad, SystemTime::UNIX_EPOCH), named_reason);
+        assert_eq!(vec![("KeyF13".to_string(), ShortcutState::Released, Some(ReleaseReason::DeviceLost))], events);
+        assert!(device_states(&mut matcher, &keyboard, Key::KeyF13, 0).is_empty());
+    }
+
+    fn run_policy(policy: MatchPolicy, keys: &[(Key, i32)]) -> Vec<ShortcutState> {
+        let mut matcher = ShortcutMatcher::new();
+        matcher.set_policy(policy);
+        matcher.add("<Ctrl>-KeyP".parse().unwrap());
+        feed(&mut matcher, keys, state)
+    }
+
+    #[test_case(& [(Key::KeyP, 1), (Key::KeyLeftCtrl, 1)] => Vec::<ShortcutState>::new(); "key before modifier")]
+    #[test_case(& [(Key::KeyLeftCtrl, 1), (Key::KeyP, 1)] => vec![ShortcutState::Pressed]; "modifier before key")]
+    #[test_case(& [(Key::KeyP, 1), (Key::KeyLeftCtrl, 1), (Key::KeyP, 0), (Key::KeyP, 1)] => vec![ShortcutState::Pressed]; "key pressed again")]
+    #[test_case(& [(Key::KeyLeftCtrl, 1), (Key::KeyP, 1), (Key::KeyLeftAlt, 1), (Key::KeyLeftAlt, 0)] => vec![
+        ShortcutState::Pressed, ShortcutState::Released, ShortcutState::Pressed,
+    ]; "rearm after extra modifier")]
+    fn strict_order_policy(keys: &[(Key, i32)]) -> Vec<ShortcutState> {
+        run_policy(MatchPolicy {
+            strict_order: true,
+            ..MatchPolicy::default()
+        }, keys)
+    }
+
+    #[test_case(& [(Key::KeyLeftCtrl, 1), (Key::KeyP, 1), (Key::KeyLeftAlt, 1), (Key::KeyLeftAlt, 0)] => vec![
+        ShortcutState::Pressed, ShortcutState::Released,
+    ]; "extra modifier")]
+    #[test_case(& [(Key::KeyLeftCtrl, 1), (Key::KeyP, 1), (Key::KeyLeftAlt, 1), (Key::KeyLeftAlt, 0), (Key::KeyP, 0), (Key::KeyP, 1)] => vec![
+        ShortcutState::Pressed, ShortcutState::Released, ShortcutState::Pressed,
+    ]; "key pressed again")]
+    #[test_case(& [(Key::KeyP, 1), (Key::KeyLeftCtrl, 1)] => vec![ShortcutState::Pressed]; "key before modifier")]
+    fn no_rearm_policy(keys: &[(Key, i32)]) -> Vec<ShortcutState> {
+        run_policy(MatchPolicy {
+            rearm: false,
+            ..MatchPolicy::default()
+        }, keys)
+    }
+
+    #[test]
+    fn per_shortcut_policy() {
+        let mut matcher = ShortcutMatcher::new();
+        matcher.add_with("<Ctrl>-KeyP".parse().unwrap(), ShortcutOptions {
+            policy: Some(MatchPolicy {
+                allow_extra_modifiers: true,
+                ..MatchPolicy::default()
+            }),
+            ..ShortcutOptions::default()
+        });
+        matcher.add("<Ctrl>-KeyO".parse().unwrap());
+        let time = SystemTime::UNIX_EPOCH;
+        assert_eq!(0, matcher.handle_key(Key::KeyLeftCtrl, 1, time).count());
+        assert_eq!(0, matcher.handle_key(Key::KeyLeftShift, 1, time).count());
+        assert_eq!(1, matcher.handle_key(Key::KeyP, 1, time).count());
+        assert_eq!(0, matcher.handle_key(Key::KeyO, 1, time).count());
+    }
+
+    fn pressed(event: ShortcutEvent) -> Option<String> {
+        (event.state == ShortcutState::Pressed).then(|| event.shortcut.to_string())
+    }
+
+    #[test_case(false, & [(Key::KeyLeftCtrl, 1), (Key::KeyP, 1)] => vec!["<Ctrl>-KeyP", "<LeftCtrl>-KeyP"]; "both")]
+    #[test_case(true, & [(Key::KeyLeftCtrl, 1), (Key::KeyP, 1)] => vec!["<LeftCtrl>-KeyP"]; "most specific")]
+    #[test_case(true, & [(Key::KeyRightCtrl, 1), (Key::KeyP, 1)] => vec!["<Ctrl>-KeyP"]; "only less specific")]
+    fn most_specific_wins(enabled: bool, keys: &[(Key, i32)]) -> Vec<String> {
+        let mut matcher = ShortcutMatcher::new();
+        matcher.set_most_specific_wins(enabled);
+        matcher.add("<Ctrl>-KeyP".parse().unwrap());
+        matcher.add("<LeftCtrl>-KeyP".parse().unwrap());
+        feed(&mut matcher, keys, pressed)
+    }
+
+    #[test]
+    fn most_specific_wins_extra_modifiers() {
+        let mut matcher = ShortcutMatcher::new();
+        matcher.set_most_specific_wins(true);
+        matcher.set_policy(MatchPolicy {
+            allow_extra_modifiers: true,
+            ..MatchPolicy::default()
+        });
+        matcher.add("<Ctrl>-KeyP".parse().unwrap());
+        matcher.add("<Ctrl><Shift>-KeyP".parse().unwrap());
+        assert_eq!(vec!["<Ctrl><Shift>-KeyP"], feed(&mut matcher, &[(Key::KeyLeftCtrl, 1), (Key::KeyLeftShift, 1), (Key::KeyP, 1)], pressed));
+    }
+
+    #[test_case(false => vec!["<Ctrl>", "<Ctrl><Shift>"]; "both")]
+    #[test_case(true => vec!["<Ctrl><Shift>"]; "most specific")]
+    fn most_specific_wins_modifier_only(enabled: bool) -> Vec<String> {
+        let mut matcher = ShortcutMatcher::new();
+        matcher.set_most_specific_wins(enabled);
+        matcher.set_policy(MatchPolicy {
+            allow_extra_modifiers: true,
+            ..MatchPolicy::default()
+        });
+        matcher.add("<Ctrl>".parse().unwrap());
+        matcher.add("<Ctrl><Shift>".parse().unwrap());
+        feed(&mut matcher, &[(Key::KeyLeftShift, 1), (Key::KeyLeftCtrl, 1)], pressed)
+    }
+
+    #[test_case(& [] => Vec::<ShortcutState>::new(); "blocked by default")]
+    #[test_case(& [Modifier::Fn, Modifier::CapsLock] => vec![ShortcutState::Pressed, ShortcutState::Released]; "ignored")]
+    fn held_fn_and_caps_lock(ignored: &[Modifier]) -> Vec<ShortcutState> {
+        let mut matcher = ShortcutMatcher::new();
+        matcher.set_policy(MatchPolicy {
+            ignored_modifiers: ModifierList::new(ignored),
+            ..MatchPolicy::default()
+        });
+        matcher.add("KeyA".parse().unwrap());
+        let keys = [(Key::KeyFn, 1), (Key::KeyCapsLock, 1), (Key::KeyA, 1), (Key::KeyA, 0)];
+        feed(&mut matcher, &keys, state)
+    }
+
+    #[test]
+    fn insert_checked() {
+        let mut set = ShortcutSet::new();
+        assert_eq!(Ok(true), set.insert_checked("<Ctrl>-KeyP".parse().unwrap(), false));
+        assert_eq!(Ok(false), set.insert_checked("<Ctrl>-KeyP".parse().unwrap(), false));
+        assert!(set.insert_checked("<LeftCtrl>-KeyP".parse().unwrap(), false).is_err());
+        assert_eq!(Ok(true), set.insert_checked("<LeftCtrl>-KeyP".parse().unwrap(), true));
+        assert!(set.insert_checked("<RightCtrl>-KeyP".parse().unwrap(), true).is_err());
+        assert_eq!(2, set.shortcut_count());
+    }
+
+    #[test]
+    fn custom_modifier_change() {
+        let states = run(&["<KeySpace>-KeyJ"], &[(Key::KeyJ, 1), (Key::KeySpace, 1), (Key::KeySpace, 0), (Key::KeyJ, 0)], state);
+        assert_eq!(vec![ShortcutState::Pressed, ShortcutState::Released], states);
+    }
+
+    fn run_chords(keys: &[(Key, i32, u64)]) -> Vec<(String, ShortcutState)> {
+        let mut matcher = ShortcutMatcher::new();
+        matcher.add_chord("KeyJ+KeyK".parse().unwrap()).unwrap();
+        matcher.add_chord("KeyA+KeyS+KeyD".parse().unwrap()).unwrap();
+        keys.iter()
+            .flat_map(|(key, value, millis)| {
+                let time = SystemTime::UNIX_EPOCH + Duration::from_millis(*millis);
+                matcher.handle_key(*key, *value, time).collect::<Vec<_>>()
+            })
+            .filter_map(|event| match event {
+                MatchEvent::Chord(event) => Some((event.chord.to_string(), event.state)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test_case(& [(Key::KeyJ, 1, 0), (Key::KeyK, 1, 10), (Key::KeyK, 0, 100), (Key::KeyJ, 0, 110)] => vec![
+        ("KeyJ+KeyK".to_string(), ShortcutState::Pressed),
+        ("KeyJ+KeyK".to_string(), ShortcutState::Released),
+    ]; "pressed and released once")]
+    #[test_case(& [(Key::KeyK, 1, 0), (Key::KeyJ, 1, 10), (Key::KeyJ, 0, 100)] => vec![
+        ("KeyJ+KeyK".to_string(), ShortcutState::Pressed),
+        ("KeyJ+KeyK".to_string(), ShortcutState::Released),
+    ]; "any order")]
+    #[test_case(& [(Key::KeyJ, 1, 0), (Key::KeyK, 1, 100)] => vec![]; "outside window")]
+    #[test_case(& [(Key::KeyLeftCtrl, 1, 0), (Key::KeyJ, 1, 0), (Key::KeyK, 1, 10)] => vec![]; "with modifier")]
+    #[test_case(& [(Key::KeyA, 1, 0), (Key::KeyS, 1, 10), (Key::KeyD, 1, 20), (Key::KeyD, 0, 100)] => vec![
+        ("KeyA+KeyS+KeyD".to_string(), ShortcutState::Pressed),
+        ("KeyA+KeyS+KeyD".to_string(), ShortcutState::Released),
+    ]; "three keys")]
+    #[test_case(& [(Key::KeyJ, 1, 0), (Key::KeyK, 1, 10), (Key::KeyD, 1, 20), (Key::KeyD, 0, 100)] => vec![
+        ("KeyJ+KeyK".to_string(), ShortcutState::Pressed),
+    ]; "other key while pressed")]
+    fn chord_test(keys: &[(Key, i32, u64)]) -> Vec<(String, ShortcutState)> {
+        run_chords(keys)
+    }
+
+    #[test]
+    fn chord_conflicts() {
+        let mut set = ShortcutSet::new();
+        assert!(set.insert_chord("KeyJ+KeyK".parse().unwrap()).unwrap());
+        assert!(!set.insert_chord("KeyK+KeyJ".parse().unwrap()).unwrap());
+        // chords that only share some keys can be told apart
+        assert!(set.insert_chord("KeyK+KeyL".parse().unwrap()).unwrap());
+        let conflict = set.insert_chord("KeyJ+KeyK+KeyL".parse().unwrap()).unwrap_err();
+        assert_eq!("KeyJ+KeyK".parse::<Chord>().unwrap(), conflict.existing);
+        assert!(set.insert_chord("KeyH+KeyJ+KeyK".parse().unwrap()).is_err());
+        assert_eq!(2, set.chords().count());
+    }
+
+    #[test_case(& [(Key::KeyLeftCtrl, 1), (Key::KeyLeftShift, 1), (Key::KeyLeftShift, 0), (Key::KeyLeftCtrl, 0)] => vec![
+        ("<Ctrl><Shift>".to_string(), ShortcutState::Pressed),
+        ("<Ctrl><Shift>".to_string(), ShortcutState::Released),
+    ]; "modifiers only")]
+    #[test_case(& [(Key::KeyLeftCtrl, 1), (Key::KeyLeftShift, 1), (Key::KeyX, 1), (Key::KeyX, 0), (Key::KeyLeftShift, 0)] => vec![
+        ("<Ctrl><Shift>".to_string(), ShortcutState::Pressed),
+        ("<Ctrl><Shift>".to_string(), ShortcutState::Cancelled),
+        ("<Ctrl><Shift>-KeyX".to_string(), ShortcutState::Pressed),
+        ("<Ctrl><Shift>-KeyX".to_string(), ShortcutState::Released),
+    ]; "cancelled by key")]
+    #[test_case(& [(Key::KeyLeftCtrl, 1), (Key::KeyLeftShift, 1), (Key::KeyLeftAlt, 1)] => vec![
+        ("<Ctrl><Shift>".to_string(), ShortcutState::Pressed),
+        ("<Ctrl><Shift>".to_string(), ShortcutState::Cancelled),
+    ]; "cancelled by modifier")]
+    #[test_case(& [(Key::KeyX, 1), (Key::KeyLeftCtrl, 1), (Key::KeyLeftShift, 1), (Key::KeyX, 0)] => vec![
+        ("<Ctrl><Shift>-KeyX".to_string(), ShortcutState::Pressed),
+        ("<Ctrl><Shift>-KeyX".to_string(), ShortcutState::Released),
+    ]; "not pressed by releasing key")]
+    fn modifier_only(keys: &[(Key, i32)]) -> Vec<(String, ShortcutState)> {
+        run(&["<Ctrl><Shift>", "<Ctrl><Shift>-KeyX"], keys, named_state)
+    }
+
+    #[test_case("@<Meta>-KeyN", & [(Key::KeyLeftMeta, 1), (Key::KeyN, 1)] => Vec::<ShortcutState>::new(); "not on press")]
+    #[test_case("@<Meta>-KeyN", & [(Key::KeyLeftMeta, 1), (Key::KeyN, 1), (Key::KeyN, 2), (Key::KeyN, 0)] => vec![
+        ShortcutState::Pressed,
+        ShortcutState::Released,
+    ]; "on key release")]
+    #[test_case("@<Meta>-KeyN", & [(Key::KeyLeftMeta, 1), (Key::KeyN, 1), (Key::KeyLeftMeta, 0)] => vec![
+        ShortcutState::Pressed,
+        ShortcutState::Released,
+    ]; "on modifier release")]
+    #[test_case("@<Meta>-KeyN", & [(Key::KeyLeftMeta, 1), (Key::KeyN, 1), (Key::KeyLeftShift, 1), (Key::KeyN, 0)] => Vec::<ShortcutState>::new(); "disarmed by modifier")]
+    #[test_case("@KeySpace", & [(Key::KeySpace, 1), (Key::KeyJ, 1), (Key::KeySpace, 0)] => vec![
+        ShortcutState::Pressed,
+        ShortcutState::Released,
+    ]; "release with other key")]
+    #[test_case("@!KeySpace", & [(Key::KeySpace, 1), (Key::KeyJ, 1), (Key::KeySpace, 0)] => Vec::<ShortcutState>::new(); "release alone with other key")]
+    #[test_case("@!KeySpace", & [(Key::KeySpace, 1), (Key::KeySpace, 0)] => vec![
+        ShortcutState::Pressed,
+        ShortcutState::Released,
+    ]; "release alone")]
+    #[test_case("@!<Ctrl><Shift>", & [(Key::KeyLeftCtrl, 1), (Key::KeyLeftShift, 1), (Key::KeyLeftShift, 0)] => vec![
+        ShortcutState::Pressed,
+        ShortcutState::Released,
+    ]; "modifiers only")]
+    #[test_case("@<Ctrl><Shift>", & [(Key::KeyLeftCtrl, 1), (Key::KeyLeftShift, 1), (Key::KeyX, 1), (Key::KeyLeftShift, 0)] => Vec::<ShortcutState>::new(); "modifiers only cancelled")]
+    fn release_trigger(shortcut: &str, keys: &[(Key, i32)]) -> Vec<ShortcutState> {
+        run(&[shortcut], keys, state)
+    }
+
+    #[test]
+    fn sync_device_keys() {
+        let device = Arc::new(DeviceInfo::from(std::path::Path::new("/dev/input/event0")));
+        let mut matcher = ShortcutMatcher::new();
+        matcher.add("<Ctrl>-KeyJ".parse().unwrap());
+        matcher.add("KeyK".parse().unwrap());
+
+        let held: KeySet = [Key::KeyLeftCtrl, Key::KeyJ].into_iter().collect();
+        let events = shortcut_events(matcher.sync_device_keys(Some(&device), &held, SystemTime::UNIX_EPOCH), named_state);
+        assert_eq!(vec![("<Ctrl>-KeyJ".to_string(), ShortcutState::Pressed)], events);
+
+        let held: KeySet = [Key::KeyK].into_iter().collect();
+        let events = shortcut_events(matcher.sync_device_keys(Some(&device), &held, SystemTime::UNIX_EPOCH), named_state);
+        assert_eq!(vec![
+            ("<Ctrl>-KeyJ".to_string(), ShortcutState::Released),
+            ("KeyK".to_string(), ShortcutState::Pressed),
+        ], events);
+    }
+
+    #[test]
+    fn remove_device() {
+        let keyboard = Arc::new(DeviceInfo::from(std::path::Path::new("/dev/input/event0")));
+        let other = Arc::new(DeviceInfo::from(std::path::Path::new("/dev/input/event1")));
+        let mut matcher = ShortcutMatcher::new();
+        matcher.add("KeyJ".parse().unwrap());
+        matcher.add("KeyK".parse().unwrap());
+        for (device, key) in [(&keyboard, Key::KeyJ), (&keyboard, Key::KeyK), (&other, Key::KeyK)] {
+            matcher.handle_device_key(Some(device), key, 1, SystemTime::UNIX_EPOCH).for_each(drop);
+        }
+
+        let events = shortcut_events(matcher.remove_device(&keyboard, SystemTime::UNIX_EPOCH), named_reason);
+        // KeyK is still held on the other device
+        assert_eq!(vec![("KeyJ".to_string(), ShortcutState::Released, Some(ReleaseReason::DeviceLost))], events);
+
+        let events: Vec<_> = matcher.handle_device_key(Some(&other), Key::KeyK, 0, SystemTime::UNIX_EPOCH).collect();
+        assert!(matches!(&events[..], [MatchEvent::Shortcut(event)] if event.reason == Some(ReleaseReason::KeyReleased)));
+    }
+
+    #[test]
+    fn releases_before_presses_in_registration_order() {
+        let mut matcher = ShortcutMatcher::new();
+        for shortcut in ["<Ctrl>-KeyK", "KeyJ", "<Ctrl>-KeyJ", "KeyK"] {
+            matcher.add(shortcut.parse().unwrap());
+        }
+        matcher.handle_key(Key::KeyJ, 1, SystemTime::UNIX_EPOCH).for_each(drop);
+        matcher.handle_key(Key::KeyK, 1, SystemTime::UNIX_EPOCH).for_each(drop);
+
+        let events = shortcut_events(matcher.handle_key(Key::KeyLeftCtrl, 1, SystemTime::UNIX_EPOCH), named_reason);
+        assert_eq!(vec![
+            ("KeyJ".to_string(), ShortcutState::Released, Some(ReleaseReason::KeyReleased)),
+            ("KeyK".to_string(), ShortcutState::Released, Some(ReleaseReason::KeyReleased)),
+            ("<Ctrl>-KeyK".to_string(), ShortcutState::Pressed, None),
+            ("<Ctrl>-KeyJ".to_string(), ShortcutState::Pressed, None),
+        ], events);
+    }
+
+    #[test]
+    fn remove_releases_pressed_shortcut() {
+        let mut matcher = ShortcutMatcher::new();
+        matcher.add("KeyA".parse().unwrap());
+        matcher.add("KeyB".parse().unwrap());
+        matcher.handle_key(Key::KeyA, 1, SystemTime::UNIX_EPOCH).for_each(drop);
+
+        assert!(matcher.remove(&"KeyB".parse().unwrap()));
+        assert_eq!(0, matcher.tick(SystemTime::UNIX_EPOCH).count());
+        assert!(matcher.remove(&"KeyA".parse().unwrap()));
+        assert_eq!(vec![
+            ("KeyA".to_string(), ShortcutState::Released, Some(ReleaseReason::ShortcutRemoved)),
+        ], shortcut_events(matcher.tick(SystemTime::UNIX_EPOCH), named_reason));
+        assert!(matcher.handle_key(Key::KeyA, 0, SystemTime::UNIX_EPOCH).next().is_none());
+    }
+
+    #[test]
+    fn release_all() {
+        let mut matcher = ShortcutMatcher::new();
+        matcher.add("KeyA".parse().unwrap());
+        matcher.add("@KeyB".parse().unwrap());
+        matcher.handle_key(Key::KeyA, 1, SystemTime::UNIX_EPOCH).for_each(drop);
+        matcher.handle_key(Key::KeyB, 1, SystemTime::UNIX_EPOCH).for_each(drop);
+
+        // shortcuts that trigger on release don't trigger when listening stops
+        assert_eq!(vec![
+            ("KeyA".to_string(), ShortcutState::Released, Some(ReleaseReason::ListenerStopped)),
+        ], shortcut_events(matcher.release_all(SystemTime::UNIX_EPOCH), named_reason));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,163 @@
+use crate::{Shortcut, Trigger};
+use parse_display::ParseError;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// A shortcut consisting of multiple strokes that have to be pressed after each other
+///
+/// The textual form separates the strokes by whitespace, each stroke uses the [Shortcut] syntax.
+///
+/// Example:
+///
+/// ```rust
+/// # use evdev_shortcut::KeySequence;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let save: KeySequence = "<Ctrl>-KeyX <Ctrl>-KeyS".parse()?;
+/// assert_eq!(2, save.steps.len());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct KeySequence {
+    pub steps: Vec<Shortcut>,
+}
+
+impl KeySequence {
+    pub fn new(steps: Vec<Shortcut>) -> Self {
+        KeySequence { steps }
+    }
+
+    /// Check if the sequence can be told apart from another sequence while it's being typed
+    ///
+    /// Two sequences are ambiguous if one is a prefix of the other,
+    /// or if a stroke in their common prefix can be triggered by the same keys as a different stroke.
+    pub fn is_ambiguous_with(&self, other: &KeySequence) -> bool {
+        for (step, other_step) in self.steps.iter().zip(other.steps.iter()) {
+            if step != other_step {
+                return step.overlaps(other_step);
+            }
+        }
+        true
+    }
+}
+
+impl FromStr for KeySequence {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let steps = s
+            .split_whitespace()
+            .map(Shortcut::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        if steps.is_empty() {
+            return Err(ParseError::with_message("Empty key sequence"));
+        }
+        if steps.iter().any(|step| step.key.is_none()) {
+            return Err(ParseError::with_message("Every stroke of a key sequence needs a key"));
+        }
+        if steps.iter().any(|step| step.trigger != Trigger::Press) {
+            return Err(ParseError::with_message("Strokes of a key sequence trigger on press"));
+        }
+        Ok(KeySequence { steps })
+    }
+}
+
+impl Display for KeySequence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (index, step) in self.steps.iter().enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+/// Error returned when a key sequence can't be told apart from an already registered binding
+#[derive(Debug, Clone, Error)]
+pub enum SequenceConflict {
+    #[error("The first stroke of {sequence} is registered as shortcut {shortcut}")]
+    Shortcut {
+        sequence: KeySequence,
+        shortcut: Shortcut,
+    },
+    #[error("{sequence} is ambiguous with {existing}")]
+    Sequence {
+        sequence: KeySequence,
+        existing: KeySequence,
+    },
+}
+
+/// Progress of a key sequence
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum SequenceState {
+    /// The strokes typed so far are the start of one or more sequences
+    InProgress,
+    /// All strokes of the sequence have been typed
+    Completed,
+    /// A key that doesn't continue any sequence was pressed, or the next stroke wasn't typed in time
+    Aborted,
+}
+
+impl SequenceState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SequenceState::InProgress => "in progress",
+            SequenceState::Completed => "completed",
+            SequenceState::Aborted => "aborted",
+        }
+    }
+}
+
+impl Display for SequenceState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Event emitted when a key sequence progresses, completes or is aborted
+///
+/// For [InProgress](SequenceState::InProgress) and [Aborted](SequenceState::Aborted) events,
+/// `sequence` holds the strokes that were typed so far.
+#[derive(Debug, Clone)]
+pub struct SequenceEvent {
+    pub sequence: KeySequence,
+    pub state: SequenceState,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KeySequence;
+    use test_case::test_case;
+
+    #[test_case("KeyA")]
+    #[test_case("<Ctrl>-KeyX <Ctrl>-KeyS")]
+    #[test_case("KeySpace KeyF KeyF")]
+    fn sequence_parse_display_test(s: &str) {
+        let sequence: KeySequence = s.parse().unwrap();
+        assert_eq!(s, sequence.to_string());
+    }
+
+    #[test_case("")]
+    #[test_case("  ")]
+    #[test_case("<Ctrl>-KeyX <Foo>-KeyS")]
+    #[test_case("<Ctrl>-KeyX <Ctrl>")]
+    #[test_case("<Ctrl>-KeyX @KeyS")]
+    fn sequence_parse_error(s: &str) {
+        assert!(s.parse::<KeySequence>().is_err());
+    }
+
+    #[test_case("<Ctrl>-KeyX KeyA", "<Ctrl>-KeyX KeyB" => false; "shared prefix")]
+    #[test_case("<Ctrl>-KeyX", "<Ctrl>-KeyX KeyB" => true; "prefix")]
+    #[test_case("<Ctrl>-KeyX KeyA", "<LeftCtrl>-KeyX KeyB" => true; "overlapping stroke")]
+    #[test_case("<LeftCtrl>-KeyX KeyA", "<RightCtrl>-KeyX KeyA" => false; "different sides")]
+    #[test_case("KeyA KeyB", "KeyB KeyA" => false; "different start")]
+    fn ambiguous_test(a: &str, b: &str) -> bool {
+        let a: KeySequence = a.parse().unwrap();
+        let b: KeySequence = b.parse().unwrap();
+        assert_eq!(a.is_ambiguous_with(&b), b.is_ambiguous_with(&a));
+        a.is_ambiguous_with(&b)
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{Key, Modifier};
+use parse_display::ParseError;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// Key or modifier that can be tapped
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum TapKey {
+    /// Tap of a modifier, a combined modifier like `<Meta>` can be tapped with either side
+    Modifier(Modifier),
+    Key(Key),
+}
+
+impl TapKey {
+    /// Check if pressing the key counts as pressing this tap key
+    pub fn matches(&self, key: Key) -> bool {
+        match self {
+            TapKey::Modifier(modifier) => Modifier::mask_from_key(key) & modifier.mask() != 0,
+            TapKey::Key(tap_key) => *tap_key == key,
+        }
+    }
+}
+
+impl Display for TapKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TapKey::Modifier(modifier) => write!(f, "<{}>", modifier),
+            TapKey::Key(key) => write!(f, "{}", key),
+        }
+    }
+}
+
+impl FromStr for TapKey {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix('<').and_then(|s| s.strip_suffix('>')) {
+            Some(modifier) => Ok(TapKey::Modifier(modifier.parse()?)),
+            None => Ok(TapKey::Key(s.parse()?)),
+        }
+    }
+}
+
+/// A key or modifier that is tapped on its own one or more times
+///
+/// A tap only counts if no other key is held or pressed while the key is down,
+/// and the key is released within the [tap timeout](crate::ShortcutMatcher::set_tap_timeout).
+/// This allows binding a lone modifier tap without interfering with the shortcuts that use the modifier.
+///
+/// Consecutive taps of the same key are counted as long as each tap follows the previous one
+/// within the [multi-tap window](crate::ShortcutMatcher::set_multi_tap_window).
+/// Once the taps stop, or no binding for the key has a higher count, the binding with the highest count
+/// that doesn't exceed the number of taps triggers. So a single tap doesn't trigger while it could still become a double tap.
+///
+/// The textual form is `tap:` followed by the key or modifier, and `*` followed by the count for multi-taps.
+///
+/// Example:
+///
+/// ```rust
+/// # use evdev_shortcut::{Tap, TapKey, Modifier};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let launcher: Tap = "tap:<Meta>".parse()?;
+/// assert_eq!(TapKey::Modifier(Modifier::Meta), launcher.key);
+///
+/// let caps_word: Tap = "tap:<LeftShift>*2".parse()?;
+/// assert_eq!(2, caps_word.count);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Tap {
+    pub key: TapKey,
+    pub count: u8,
+}
+
+impl Tap {
+    pub fn new(key: TapKey) -> Self {
+        Tap { key, count: 1 }
+    }
+
+    /// Create a tap that has to be tapped `count` times in a row
+    pub fn multi(key: TapKey, count: u8) -> Self {
+        Tap { key, count }
+    }
+}
+
+impl FromStr for Tap {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tap = s
+            .strip_prefix("tap:")
+            .ok_or_else(|| ParseError::with_message("Tap should start with tap:"))?;
+        let (key, count) = match tap.split_once('*') {
+            Some((key, count)) => (key, count.parse().map_err(|_| ParseError::with_message("Invalid tap count"))?),
+            None => (tap, 1),
+        };
+        if count == 0 {
+            return Err(ParseError::with_message("Invalid tap count"));
+        }
+        Ok(Tap { key: key.parse()?, count })
+    }
+}
+
+impl Display for Tap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "tap:{}", self.key)?;
+        if self.count != 1 {
+            write!(f, "*{}", self.count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Event emitted when a tap is triggered
+///
+/// `count` is the number of consecutive taps, which can exceed the count of the tap binding
+/// if no binding for the key has a higher count.
+#[derive(Debug, Clone)]
+pub struct TapEvent {
+    pub tap: Tap,
+    pub count: u8,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Tap, TapKey};
+    use crate::{Key, Modifier};
+    use test_case::test_case;
+
+    #[test_case("tap:<Meta>", Tap::new(TapKey::Modifier(Modifier::Meta)))]
+    #[test_case("tap:<LeftShift>*2", Tap::multi(TapKey::Modifier(Modifier::LeftShift), 2))]
+    #[test_case("tap:KeyEsc", Tap::new(TapKey::Key(Key::KeyEsc)))]
+    #[test_case("tap:KeyEsc*3", Tap::multi(TapKey::Key(Key::KeyEsc), 3))]
+    fn tap_parse_display_test(s: &str, tap: Tap) {
+        assert_eq!(s, tap.to_string());
+        assert_eq!(tap, s.parse().unwrap());
+    }
+
+    #[test_case("<Meta>")]
+    #[test_case("tap:<Foo>")]
+    #[test_case("tap:")]
+    #[test_case("tap:KeyEsc*0")]
+    #[test_case("tap:KeyEsc*")]
+    fn tap_parse_error(s: &str) {
+        assert!(s.parse::<Tap>().is_err());
+    }
+
+    #[test_case(TapKey::Modifier(Modifier::Meta), Key::KeyRightMeta => true)]
+    #[test_case(TapKey::Modifier(Modifier::LeftMeta), Key::KeyRightMeta => false)]
+    #[test_case(TapKey::Modifier(Modifier::Meta), Key::KeyM => false)]
+    #[test_case(TapKey::Key(Key::KeyEsc), Key::KeyEsc => true)]
+    fn tap_key_matches(tap: TapKey, key: Key) -> bool {
+        tap.matches(key)
+    }
+}